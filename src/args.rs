@@ -0,0 +1,134 @@
+use crate::clock::{SyncMode, Tempo};
+
+pub(crate) const USAGE: &str = "\
+Usage: MidiSequencer [OPTIONS]
+       MidiSequencer validate [OPTIONS] [SCALES] [SEQUENCES]
+
+Options:
+  --scales <PATH>        Scale file
+  --sequences <PATH>     Sequence file
+  --project <PATH>       Project file, used instead of the scale and sequence files
+  --input-port <NAME>    Name of the MIDI input port
+  --output-port <NAME>   Name of the MIDI output port
+  --sync <MODE>          external (MIDI clock) or internal
+  --bpm <BPM>            Tempo of the internal clock
+  -h, --help             Show this help
+
+Files not given are looked up in the data and config directories
+($XDG_DATA_HOME/microtonal-sequencer, $XDG_CONFIG_HOME/microtonal-sequencer), then in
+./data. The built-in scales and sequences are used when none is found.";
+
+/// What the command line asks for. Settings not given keep the ones of the loaded files.
+#[derive(Default)]
+pub(crate) struct Args {
+    /// Check the files and exit, instead of starting the sequencer.
+    pub(crate) validate: bool,
+    pub(crate) help: bool,
+    pub(crate) scale_path: Option<String>,
+    pub(crate) sequence_path: Option<String>,
+    pub(crate) project_path: Option<String>,
+    pub(crate) input_port: Option<String>,
+    pub(crate) output_port: Option<String>,
+    pub(crate) sync: Option<SyncMode>,
+    pub(crate) bpm: Option<f64>,
+}
+
+/// Reads the arguments, without the program name. Options take their value as the next
+/// argument or after `=`, like `--sync=internal`.
+pub(crate) fn parse_args(args: impl IntoIterator<Item = String>) -> Result<Args, String> {
+    let mut parsed = Args::default();
+    let mut positional = vec![];
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        let (option, inline_value) = match arg.split_once('=') {
+            Some((option, value)) if arg.starts_with("--") => (option, Some(value.to_string())),
+            _ => (arg.as_str(), None),
+        };
+        let mut value = || {
+            inline_value
+                .clone()
+                .or_else(|| args.next())
+                .ok_or(format!("{} needs a value", option))
+        };
+        match option {
+            "-h" | "--help" => parsed.help = true,
+            "--scales" => parsed.scale_path = Some(value()?),
+            "--sequences" => parsed.sequence_path = Some(value()?),
+            "--project" => parsed.project_path = Some(value()?),
+            "--input-port" => parsed.input_port = Some(value()?),
+            "--output-port" => parsed.output_port = Some(value()?),
+            "--sync" => parsed.sync = Some(parse_sync(&value()?)?),
+            "--bpm" => parsed.bpm = Some(parse_bpm(&value()?)?),
+            _ if option.starts_with('-') => return Err(format!("Unknown option {}", option)),
+            "validate" if positional.is_empty() && !parsed.validate => parsed.validate = true,
+            _ => positional.push(arg),
+        }
+    }
+
+    // `validate scales.json sequences.json` is kept from before the options existed
+    let mut positional = positional.into_iter();
+    if parsed.validate {
+        parsed.scale_path = parsed.scale_path.or(positional.next());
+        parsed.sequence_path = parsed.sequence_path.or(positional.next());
+    }
+    match positional.next() {
+        Some(arg) => Err(format!("Unexpected argument {}", arg)),
+        None => Ok(parsed),
+    }
+}
+
+fn parse_sync(value: &str) -> Result<SyncMode, String> {
+    match value {
+        "external" => Ok(SyncMode::External),
+        "internal" => Ok(SyncMode::Internal),
+        _ => Err(format!("Unknown sync mode {}: use external or internal", value)),
+    }
+}
+
+fn parse_bpm(value: &str) -> Result<f64, String> {
+    match value.parse::<f64>() {
+        Ok(bpm) if (Tempo::MIN_BPM..=Tempo::MAX_BPM).contains(&bpm) => Ok(bpm),
+        _ => Err(format!(
+            "Invalid BPM {}: use a number from {} to {}",
+            value,
+            Tempo::MIN_BPM,
+            Tempo::MAX_BPM
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &[&str]) -> Result<Args, String> {
+        parse_args(args.iter().map(|arg| arg.to_string()))
+    }
+
+    #[test]
+    fn options_take_the_next_argument_or_an_inline_value() {
+        let args = parse(&["--scales", "a.json", "--sync=internal", "--bpm", "90"]).unwrap();
+        assert_eq!(args.scale_path.as_deref(), Some("a.json"));
+        assert!(args.sync == Some(SyncMode::Internal));
+        assert_eq!(args.bpm, Some(90f64));
+        assert!(!args.validate);
+    }
+
+    #[test]
+    fn validate_takes_the_files_as_arguments() {
+        let args = parse(&["validate", "a.json", "b.json"]).unwrap();
+        assert!(args.validate);
+        assert_eq!(args.scale_path.as_deref(), Some("a.json"));
+        assert_eq!(args.sequence_path.as_deref(), Some("b.json"));
+    }
+
+    #[test]
+    fn invalid_arguments_are_refused() {
+        assert!(parse(&["--scales"]).is_err());
+        assert!(parse(&["--sync", "midi"]).is_err());
+        assert!(parse(&["--bpm", "fast"]).is_err());
+        assert!(parse(&["--verbose"]).is_err());
+        assert!(parse(&["a.json"]).is_err());
+        assert!(parse(&["validate", "a.json", "b.json", "c.json"]).is_err());
+    }
+}
@@ -0,0 +1,254 @@
+use crate::note::{Note, NoteDuration, Scale};
+use crate::random::Rng;
+use std::fmt;
+use std::sync::Arc;
+
+/// Arpeggiator rates, as a display label and a duration in beats.
+pub(crate) const ARP_RATES: [(&str, f64); 6] = [
+    ("1/4", 1.0),
+    ("1/8", 0.5),
+    ("1/8T", 1.0 / 3.0),
+    ("1/16", 0.25),
+    ("1/16T", 1.0 / 6.0),
+    ("1/32", 0.125),
+];
+
+#[derive(Clone, Copy, PartialEq)]
+pub(crate) enum ArpMode {
+    Off,
+    Up,
+    Down,
+    UpDown,
+    Random,
+    AsPlayed,
+}
+
+impl ArpMode {
+    const ALL: [ArpMode; 6] = [
+        ArpMode::Off,
+        ArpMode::Up,
+        ArpMode::Down,
+        ArpMode::UpDown,
+        ArpMode::Random,
+        ArpMode::AsPlayed,
+    ];
+
+    pub(crate) fn next(self) -> Self {
+        let i = Self::ALL.iter().position(|m| *m == self).unwrap_or(0);
+        Self::ALL[(i + 1) % Self::ALL.len()]
+    }
+
+    pub(crate) fn previous(self) -> Self {
+        let i = Self::ALL.iter().position(|m| *m == self).unwrap_or(0);
+        Self::ALL[(i + Self::ALL.len() - 1) % Self::ALL.len()]
+    }
+}
+
+impl fmt::Display for ArpMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            ArpMode::Off => "Off",
+            ArpMode::Up => "Up",
+            ArpMode::Down => "Down",
+            ArpMode::UpDown => "Up-down",
+            ArpMode::Random => "Random",
+            ArpMode::AsPlayed => "As played",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// Arpeggiates the notes held on the MIDI input.
+///
+/// Incoming MIDI numbers are quantized to the degrees of the scale that is playing, so the
+/// arpeggio always stays in the tuning, whatever the controller sends.
+///
+/// - `held` keeps the held notes in the order they were played, with their velocity.
+/// - `position` is the index of the next note to play in the ordered held notes.
+/// - `ticks` counts the clock ticks since the last arpeggiated note.
+pub(crate) struct Arpeggiator {
+    pub(crate) mode: ArpMode,
+    pub(crate) rate_index: usize,
+    held: Vec<(u8, u8)>,
+    position: usize,
+    ascending: bool,
+    ticks: u8,
+    rng: Rng,
+}
+
+impl Arpeggiator {
+    pub(crate) fn new() -> Self {
+        Self {
+            mode: ArpMode::Off,
+            rate_index: 1,
+            held: vec![],
+            position: 0,
+            ascending: true,
+            ticks: 0,
+            rng: Rng::from_time(),
+        }
+    }
+
+    pub(crate) fn rate(&self) -> NoteDuration {
+        NoteDuration {
+            duration: ARP_RATES[self.rate_index].1,
+        }
+    }
+
+    pub(crate) fn rate_name(&self) -> &'static str {
+        ARP_RATES[self.rate_index].0
+    }
+
+    pub(crate) fn is_playing(&self) -> bool {
+        self.mode != ArpMode::Off && !self.held.is_empty()
+    }
+
+    pub(crate) fn note_on(&mut self, number: u8, velocity: u8) {
+        if !self.held.iter().any(|(n, _)| *n == number) {
+            if self.held.is_empty() {
+                self.position = 0;
+                self.ascending = true;
+                self.ticks = 0;
+            }
+            self.held.push((number, velocity));
+        }
+    }
+
+    pub(crate) fn note_off(&mut self, number: u8) {
+        self.held.retain(|(n, _)| *n != number);
+    }
+
+    /// Advances the arpeggiator by one clock tick.
+    ///
+    /// Returns the note to play when a new arpeggio step starts on this tick.
+    pub(crate) fn tick(&mut self, scale: Arc<Scale>) -> Option<Note> {
+        if !self.is_playing() {
+            return None;
+        }
+        let step_ticks = self.rate().get_tick_length().max(1);
+        let note = if self.ticks == 0 {
+            Some(self.next_note(scale))
+        } else {
+            None
+        };
+        self.ticks = (self.ticks + 1) % step_ticks;
+        note
+    }
+
+    fn ordered_held(&self) -> Vec<(u8, u8)> {
+        let mut ordered = self.held.clone();
+        match self.mode {
+            ArpMode::Up | ArpMode::UpDown => ordered.sort_by_key(|(n, _)| *n),
+            ArpMode::Down => ordered.sort_by_key(|(n, _)| std::cmp::Reverse(*n)),
+            _ => {}
+        }
+        ordered
+    }
+
+    fn next_note(&mut self, scale: Arc<Scale>) -> Note {
+        let ordered = self.ordered_held();
+        let len = ordered.len();
+
+        let (number, velocity) = match self.mode {
+            ArpMode::Random => ordered[self.rng.below(len)],
+            ArpMode::UpDown => {
+                let i = self.position.min(len - 1);
+                if len > 1 {
+                    if self.ascending && i == len - 1 {
+                        self.ascending = false;
+                    } else if !self.ascending && i == 0 {
+                        self.ascending = true;
+                    }
+                }
+                self.position = match (len, self.ascending) {
+                    (1, _) => 0,
+                    (_, true) => i + 1,
+                    (_, false) => i - 1,
+                };
+                ordered[i]
+            }
+            _ => {
+                let i = self.position % len;
+                self.position = (i + 1) % len;
+                ordered[i]
+            }
+        };
+
+        let (note_index, octave) = scale.nearest_degree(number as f64);
+        Note::new(scale, note_index, octave, self.rate(), velocity)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn twelve_tet() -> Arc<Scale> {
+        Arc::new(Scale {
+            name: "12-TET".to_string(),
+            steps: (0..12).map(|step| step as f64).collect(),
+            note_names: (0..12).map(|step| step.to_string()).collect(),
+        })
+    }
+
+    fn holding(mode: ArpMode, held: &[u8]) -> Arpeggiator {
+        let mut arpeggiator = Arpeggiator::new();
+        arpeggiator.mode = mode;
+        arpeggiator.rng = Rng::new(3);
+        for number in held {
+            arpeggiator.note_on(*number, 100);
+        }
+        arpeggiator
+    }
+
+    /// The MIDI numbers of the next `count` notes of the arpeggio.
+    fn played(arpeggiator: &mut Arpeggiator, count: usize) -> Vec<f64> {
+        (0..count)
+            .map(|_| arpeggiator.next_note(twelve_tet()).get_midi_number())
+            .collect()
+    }
+
+    #[test]
+    fn up_and_down_sort_the_held_notes() {
+        let mut up = holding(ArpMode::Up, &[64, 60, 67]);
+        assert_eq!(played(&mut up, 4), vec![60f64, 64f64, 67f64, 60f64]);
+        let mut down = holding(ArpMode::Down, &[64, 60, 67]);
+        assert_eq!(played(&mut down, 4), vec![67f64, 64f64, 60f64, 67f64]);
+        let mut as_played = holding(ArpMode::AsPlayed, &[64, 60, 67]);
+        assert_eq!(played(&mut as_played, 4), vec![64f64, 60f64, 67f64, 64f64]);
+    }
+
+    #[test]
+    fn up_down_turns_back_without_repeating_the_ends() {
+        let mut arpeggiator = holding(ArpMode::UpDown, &[67, 60, 64]);
+        assert_eq!(
+            played(&mut arpeggiator, 7),
+            vec![60f64, 64f64, 67f64, 64f64, 60f64, 64f64, 67f64]
+        );
+        let mut single = holding(ArpMode::UpDown, &[60]);
+        assert_eq!(played(&mut single, 3), vec![60f64; 3]);
+    }
+
+    #[test]
+    fn random_only_plays_held_notes() {
+        let mut arpeggiator = holding(ArpMode::Random, &[60, 64, 67]);
+        let notes = played(&mut arpeggiator, 60);
+        assert!(notes.iter().all(|n| [60f64, 64f64, 67f64].contains(n)));
+        for held in [60f64, 64f64, 67f64] {
+            assert!(notes.contains(&held));
+        }
+    }
+
+    #[test]
+    fn notes_start_at_the_rate() {
+        // 1/8 at 24 ticks per beat
+        let mut arpeggiator = holding(ArpMode::Up, &[60, 64]);
+        let started: Vec<usize> = (0..30)
+            .filter(|_| arpeggiator.tick(twelve_tet()).is_some())
+            .collect();
+        assert_eq!(started, vec![0, 12, 24]);
+        arpeggiator.note_off(60);
+        arpeggiator.note_off(64);
+        assert!(arpeggiator.tick(twelve_tet()).is_none());
+    }
+}
@@ -1,26 +1,65 @@
+use std::fmt;
+use std::time::{Duration, Instant};
+
+/// Tick period assumed until the clock has been measured: 24 ticks per beat at 120 BPM.
+pub(crate) const DEFAULT_TICK_PERIOD: Duration = Duration::from_micros(20_833);
+
+/// Ticks the clock counts before starting again from 0: 8 beats of 24 ticks.
+const CYCLE_TICKS: u16 = 24 * 8;
+
+/// Longest duration of a note, in beats: the ticks of a note are counted within one cycle of
+/// the clock.
+pub(crate) const MAX_NOTE_BEATS: f64 = CYCLE_TICKS as f64 / 24f64;
+
 pub(crate) struct Clock {
     tick: u16,
-    last_played_tick: Option<u16>
+    last_played_tick: Option<u16>,
+    last_tick_time: Option<Instant>,
+    tick_period: Duration,
 }
 
 impl Clock {
     pub(crate) fn new() -> Self {
         Self {
             tick: 0,
-            last_played_tick: None
+            last_played_tick: None,
+            last_tick_time: None,
+            tick_period: DEFAULT_TICK_PERIOD,
         }
     }
 
+    /// Measures the time between two clock messages, to place notes between ticks.
+    ///
+    /// The period is smoothed so that the jitter of the incoming clock does not move the notes
+    /// around, and long gaps (the clock was stopped) are ignored.
+    pub(crate) fn tick_received(&mut self, now: Instant) {
+        if let Some(last) = self.last_tick_time {
+            let elapsed = now.duration_since(last);
+            if elapsed < Duration::from_millis(250) {
+                self.tick_period = self.tick_period.mul_f64(0.8) + elapsed.mul_f64(0.2);
+            }
+        }
+        self.last_tick_time = Some(now);
+    }
+
+    pub(crate) fn tick_period(&self) -> Duration {
+        self.tick_period
+    }
+
     pub(crate) fn next(&mut self) {
-        self.tick = (self.tick + 1) % (24 * 8);
+        self.tick = (self.tick + 1) % CYCLE_TICKS;
     }
 
+    /// Whether `duration` ticks passed since the last note was played. It is checked from the
+    /// tick after the note, so the ticks passed go from 1 to a whole cycle, across the end of
+    /// the cycle. Longer notes end after a cycle.
     pub(crate) fn has_time_passed_note(&self, duration: u8) -> bool {
         if duration == 0 {
             return true;
         }
         let start = self.last_played_tick.unwrap_or(0);
-        (self.tick - start) >= duration as u16
+        let passed = (self.tick + CYCLE_TICKS - start - 1) % CYCLE_TICKS + 1;
+        passed >= (duration as u16).min(CYCLE_TICKS)
     }
 
     pub(crate) fn note_played(&mut self) {
@@ -31,4 +70,90 @@ impl Clock {
         self.tick = 0;
         self.last_played_tick = None;
     }
-}
\ No newline at end of file
+}
+
+/// Where the clock ticks come from.
+#[derive(Clone, Copy, PartialEq)]
+pub(crate) enum SyncMode {
+    /// MIDI clock messages received on the input port, from a DAW for example.
+    External,
+    /// Ticks generated by the sequencer at the tempo of `Tempo::bpm`.
+    Internal,
+}
+
+impl SyncMode {
+    pub(crate) fn next(self) -> Self {
+        match self {
+            SyncMode::External => SyncMode::Internal,
+            SyncMode::Internal => SyncMode::External,
+        }
+    }
+}
+
+impl fmt::Display for SyncMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SyncMode::External => write!(f, "External MIDI clock"),
+            SyncMode::Internal => write!(f, "Internal"),
+        }
+    }
+}
+
+/// Clock settings. The sync mode is read when the sequencer starts, the tempo on every tick.
+pub(crate) struct Tempo {
+    pub(crate) sync: SyncMode,
+    pub(crate) bpm: f64,
+}
+
+impl Tempo {
+    pub(crate) const MIN_BPM: f64 = 20f64;
+    pub(crate) const MAX_BPM: f64 = 300f64;
+
+    pub(crate) fn new() -> Self {
+        Self {
+            sync: SyncMode::External,
+            bpm: 120f64,
+        }
+    }
+
+    /// Time between two ticks of the internal clock, 24 per beat like the MIDI clock.
+    pub(crate) fn tick_period(&self) -> Duration {
+        Duration::from_secs_f64(60f64 / (self.bpm.clamp(Self::MIN_BPM, Self::MAX_BPM) * 24f64))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Ticks the clock until a note of `duration` ticks played now ends.
+    fn ticks_until_played(clock: &mut Clock, duration: u8) -> u16 {
+        clock.note_played();
+        let mut ticks = 0;
+        loop {
+            clock.next();
+            ticks += 1;
+            if clock.has_time_passed_note(duration) {
+                return ticks;
+            }
+        }
+    }
+
+    #[test]
+    fn notes_end_across_the_end_of_the_cycle() {
+        let mut clock = Clock::new();
+        for _ in 0..180 {
+            clock.next();
+        }
+        assert_eq!(ticks_until_played(&mut clock, 24), 24);
+        assert_eq!(ticks_until_played(&mut clock, 24), 24);
+    }
+
+    #[test]
+    fn the_longest_notes_last_a_whole_cycle() {
+        let mut clock = Clock::new();
+        let longest = (MAX_NOTE_BEATS * 24f64) as u8;
+        assert_eq!(ticks_until_played(&mut clock, longest), CYCLE_TICKS);
+        assert_eq!(ticks_until_played(&mut clock, u8::MAX), CYCLE_TICKS);
+    }
+}
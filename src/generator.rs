@@ -0,0 +1,229 @@
+use crate::note::{Note, Scale};
+use crate::random::Rng;
+
+/// Intervals used by the random walk when none are given: mostly steps, sometimes thirds,
+/// rarely a repeated note.
+const DEFAULT_INTERVALS: &[(i32, u32)] = &[(-2, 2), (-1, 5), (0, 1), (1, 5), (2, 2)];
+
+/// How the generator chooses the degree of the next note.
+#[derive(Clone)]
+pub(crate) enum MelodyModel {
+    /// Moves by an interval, in scale degrees, drawn from a table of `(interval, weight)`.
+    RandomWalk { intervals: Vec<(i32, u32)> },
+    /// Chooses the next degree from the transitions counted in existing sequences:
+    /// `transitions[a][b]` is how many times degree `b` follows degree `a`.
+    Markov { transitions: Vec<Vec<u32>> },
+}
+
+impl MelodyModel {
+    pub(crate) fn random_walk(intervals: Vec<(i32, u32)>) -> Self {
+        if intervals.is_empty() {
+            MelodyModel::RandomWalk {
+                intervals: DEFAULT_INTERVALS.to_vec(),
+            }
+        } else {
+            MelodyModel::RandomWalk { intervals }
+        }
+    }
+
+    /// Counts the transitions between the degrees of `melodies`, each played in a loop.
+    /// The notes that are not in `scale` are ignored.
+    pub(crate) fn markov(scale: &Scale, melodies: &[&[Note]]) -> Self {
+        let scale_len = scale.steps.len();
+        let mut transitions = vec![vec![0; scale_len]; scale_len];
+        for notes in melodies {
+            let degrees: Vec<usize> = notes
+                .iter()
+                .filter(|n| n.scale.name == scale.name && n.note_index < scale_len)
+                .map(|n| n.note_index)
+                .collect();
+            for (i, from) in degrees.iter().enumerate() {
+                let to = degrees[(i + 1) % degrees.len()];
+                transitions[*from][to] += 1;
+            }
+        }
+        MelodyModel::Markov { transitions }
+    }
+
+    fn weight(&self, from: i32, to: i32, scale_len: i32) -> f64 {
+        match self {
+            MelodyModel::RandomWalk { intervals } => intervals
+                .iter()
+                .filter(|(interval, _)| *interval == to - from)
+                .map(|(_, weight)| *weight as f64)
+                .sum(),
+            MelodyModel::Markov { transitions } => {
+                let row = &transitions[from.rem_euclid(scale_len) as usize];
+                row[to.rem_euclid(scale_len) as usize] as f64
+            }
+        }
+    }
+}
+
+/// Limits of the generated melodies. Degrees are counted from the first degree of octave 0,
+/// like `octave * scale length + note_index`.
+///
+/// - `max_leap` is the largest interval between two notes, in degrees.
+/// - `tonic_gravity` makes the first degree of the scale more likely: its weight is multiplied
+///   by `1 + tonic_gravity`.
+#[derive(Clone, Copy)]
+pub(crate) struct MelodyConstraints {
+    pub(crate) lowest: i32,
+    pub(crate) highest: i32,
+    pub(crate) max_leap: u32,
+    pub(crate) tonic_gravity: f64,
+}
+
+/// Generates the melody of a sequence live, keeping the rhythm of its notes.
+///
+/// Only `note_index` and `octave` of the notes are replaced. The first note stays where it is
+/// and starts the walk, so the start of each loop does not change.
+///
+/// `regenerate_every` is the number of loops between two melodies, 0 keeping the melody until
+/// it is generated again from the TUI. `loops` counts the loops played since the last one.
+#[derive(Clone)]
+pub(crate) struct Generator {
+    pub(crate) model: MelodyModel,
+    pub(crate) constraints: MelodyConstraints,
+    pub(crate) regenerate_every: u32,
+    pub(crate) seed: Option<u64>,
+    loops: u32,
+    rng: Rng,
+}
+
+impl Generator {
+    pub(crate) fn new(
+        model: MelodyModel,
+        constraints: MelodyConstraints,
+        regenerate_every: u32,
+        seed: Option<u64>,
+    ) -> Self {
+        Self {
+            model,
+            constraints,
+            regenerate_every,
+            seed,
+            loops: 0,
+            rng: match seed {
+                Some(seed) => Rng::new(seed),
+                None => Rng::from_time(),
+            },
+        }
+    }
+
+    /// Counts a new loop of the sequence. Returns whether the melody should be generated again.
+    pub(crate) fn loop_started(&mut self) -> bool {
+        self.loops += 1;
+        if self.regenerate_every > 0 && self.loops >= self.regenerate_every {
+            self.loops = 0;
+            return true;
+        }
+        false
+    }
+
+    /// Moves the range and the largest leap to a scale of `new_len` degrees, keeping their size
+    /// in octaves.
+    pub(crate) fn rescale(&mut self, old_len: usize, new_len: usize) {
+        let rescaled = |degrees: i32| {
+            (degrees as f64 * new_len as f64 / old_len as f64).round() as i32
+        };
+        self.constraints.lowest = rescaled(self.constraints.lowest);
+        self.constraints.highest = rescaled(self.constraints.highest);
+        self.constraints.max_leap = rescaled(self.constraints.max_leap as i32).max(1) as u32;
+    }
+
+    pub(crate) fn generate(&mut self, notes: &mut [Note]) {
+        let Some(first) = notes.first() else {
+            return;
+        };
+        let scale_len = first.scale.steps.len() as i32;
+        let playable = first.scale.playable_degrees();
+        let mut degree = first.degree();
+
+        for note in notes.iter_mut().skip(1) {
+            degree = self.next_degree(degree, scale_len, playable);
+            note.set_degree(degree);
+        }
+    }
+
+    /// Picks the degree following `from`, within the range of the constraints and the
+    /// `playable` degrees of the scale.
+    fn next_degree(&mut self, from: i32, scale_len: i32, playable: (i32, i32)) -> i32 {
+        let constraints = self.constraints;
+        let max_leap = constraints.max_leap as i32;
+        let range_lowest = constraints.lowest.max(playable.0);
+        let range_highest = constraints.highest.min(playable.1).max(range_lowest);
+        let lowest = range_lowest.max(from - max_leap);
+        let highest = range_highest.min(from + max_leap);
+        if lowest > highest {
+            // The previous note is out of the range: go back into it
+            return from.clamp(range_lowest, range_highest);
+        }
+
+        let candidates: Vec<(i32, f64)> = (lowest..=highest)
+            .map(|to| {
+                let mut weight = self.model.weight(from, to, scale_len);
+                if to.rem_euclid(scale_len) == 0 {
+                    weight *= 1f64 + constraints.tonic_gravity;
+                }
+                (to, weight)
+            })
+            .collect();
+
+        let total: f64 = candidates.iter().map(|(_, weight)| weight).sum();
+        if total <= 0f64 {
+            // Nothing in the model leads anywhere in the range: any degree will do
+            return candidates[self.rng.below(candidates.len())].0;
+        }
+        let mut target = self.rng.unit() * total;
+        for (to, weight) in &candidates {
+            if target < *weight {
+                return *to;
+            }
+            target -= weight;
+        }
+        candidates[candidates.len() - 1].0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn generator(lowest: i32, highest: i32, max_leap: u32) -> Generator {
+        let constraints = MelodyConstraints {
+            lowest,
+            highest,
+            max_leap,
+            tonic_gravity: 0f64,
+        };
+        let model = MelodyModel::random_walk(vec![(-2, 1), (-1, 1), (1, 1), (2, 1)]);
+        Generator::new(model, constraints, 1, Some(7))
+    }
+
+    #[test]
+    fn next_degree_stays_in_the_range_and_the_playable_degrees() {
+        let mut generator = generator(100, 200, 2);
+        let mut degree = 120;
+        for _ in 0..200 {
+            degree = generator.next_degree(degree, 12, (0, 127));
+            assert!((100..=127).contains(&degree), "{}", degree);
+        }
+    }
+
+    #[test]
+    fn next_degree_goes_back_into_the_range() {
+        let mut generator = generator(10, 20, 2);
+        assert_eq!(generator.next_degree(60, 12, (0, 127)), 20);
+        assert_eq!(generator.next_degree(-4, 12, (0, 127)), 10);
+    }
+
+    #[test]
+    fn rescale_keeps_the_range_in_octaves() {
+        let mut generator = generator(12, 36, 6);
+        generator.rescale(12, 24);
+        let constraints = generator.constraints;
+        assert_eq!((constraints.lowest, constraints.highest), (24, 72));
+        assert_eq!(constraints.max_leap, 12);
+    }
+}
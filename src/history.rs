@@ -0,0 +1,217 @@
+use crate::note::Scale;
+use crate::sequencer::{Sequence, Sequencer};
+use std::collections::VecDeque;
+use std::sync::Arc;
+
+/// Number of edits that can be undone.
+const HISTORY_LIMIT: usize = 100;
+
+/// A change of the sequences or scales of the sequencer.
+///
+/// Applying a command returns the command that reverts it, so the same commands fill both
+/// the undo and the redo stacks.
+pub(crate) struct EditCommand {
+    change: Change,
+    /// The reloads of the sequencer when the command was made. The data reloaded since
+    /// replaced what the command would restore, so it can't be applied anymore.
+    reload_count: u64,
+}
+
+enum Change {
+    /// Puts back a sequence, for edits of its notes or settings.
+    ReplaceSequence { index: usize, sequence: Sequence },
+    /// Puts back the whole playlist and the loaded scales, for edits that add, remove or move
+    /// sequences, or change a scale shared by several sequences.
+    ReplaceAll {
+        sequences: Vec<Sequence>,
+        scales: Vec<Arc<Scale>>,
+    },
+}
+
+impl EditCommand {
+    /// The command restoring the sequence `index` as it is now.
+    pub(crate) fn sequence(sequencer: &Sequencer, index: usize) -> Self {
+        EditCommand {
+            change: Change::ReplaceSequence {
+                index,
+                sequence: sequencer.sequences[index].clone(),
+            },
+            reload_count: sequencer.reload_count(),
+        }
+    }
+
+    /// The command restoring all the sequences and scales as they are now.
+    pub(crate) fn all(sequencer: &Sequencer) -> Self {
+        EditCommand {
+            change: Change::ReplaceAll {
+                sequences: sequencer.sequences.clone(),
+                scales: sequencer.scales.clone(),
+            },
+            reload_count: sequencer.reload_count(),
+        }
+    }
+
+    /// Returns `None`, leaving the sequencer unchanged, if the sequence to put back isn't in
+    /// the playlist anymore.
+    fn apply(self, sequencer: &mut Sequencer) -> Option<Self> {
+        match self.change {
+            Change::ReplaceSequence { index, sequence } => {
+                if index >= sequencer.sequences.len() {
+                    return None;
+                }
+                let inverse = EditCommand::sequence(sequencer, index);
+                sequencer.replace_sequence(index, sequence);
+                Some(inverse)
+            }
+            Change::ReplaceAll { sequences, scales } => {
+                let inverse = EditCommand::all(sequencer);
+                sequencer.scales = scales;
+                sequencer.replace_sequences(sequences);
+                Some(inverse)
+            }
+        }
+    }
+}
+
+/// Undo and redo stacks shared by all the editing screens.
+///
+/// Undoing changes the loaded sequences and scales only: the files keep what was last saved.
+/// Reloading the files forgets the edits made before.
+pub(crate) struct History {
+    undo: VecDeque<EditCommand>,
+    redo: Vec<EditCommand>,
+}
+
+impl History {
+    pub(crate) fn new() -> Self {
+        Self {
+            undo: VecDeque::new(),
+            redo: vec![],
+        }
+    }
+
+    /// Keeps the command reverting an edit about to be made. Made edits can't be redone anymore.
+    pub(crate) fn record(&mut self, command: EditCommand) {
+        self.forget_before(command.reload_count);
+        if self.undo.len() == HISTORY_LIMIT {
+            self.undo.pop_front();
+        }
+        self.undo.push_back(command);
+        self.redo.clear();
+    }
+
+    /// Reverts the last edit. Returns `false` if there is nothing to undo.
+    pub(crate) fn undo(&mut self, sequencer: &mut Sequencer) -> bool {
+        self.forget_before(sequencer.reload_count());
+        let Some(command) = self.undo.pop_back() else {
+            return false;
+        };
+        match command.apply(sequencer) {
+            Some(inverse) => self.redo.push(inverse),
+            None => self.redo.clear(),
+        }
+        true
+    }
+
+    /// Makes the last undone edit again. Returns `false` if there is nothing to redo.
+    pub(crate) fn redo(&mut self, sequencer: &mut Sequencer) -> bool {
+        self.forget_before(sequencer.reload_count());
+        let Some(command) = self.redo.pop() else {
+            return false;
+        };
+        match command.apply(sequencer) {
+            Some(inverse) => self.undo.push_back(inverse),
+            None => self.undo.clear(),
+        }
+        true
+    }
+
+    /// Drops the commands made before the `reload_count`-th reload.
+    fn forget_before(&mut self, reload_count: u64) {
+        self.undo.retain(|command| command.reload_count >= reload_count);
+        self.redo.retain(|command| command.reload_count >= reload_count);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sequencer::Reload;
+
+    fn sequencer_with(sequence_count: usize) -> Sequencer {
+        let mut sequencer = Sequencer::placeholder();
+        for index in 1..sequence_count {
+            let sequence = sequencer.sequences[0].clone();
+            sequencer.insert_sequence(index, sequence);
+        }
+        sequencer
+    }
+
+    #[test]
+    fn undo_restores_the_sequence() {
+        let mut sequencer = sequencer_with(2);
+        let mut history = History::new();
+        history.record(EditCommand::sequence(&sequencer, 1));
+        sequencer.sequences[1].repeat = 3;
+
+        assert!(history.undo(&mut sequencer));
+        assert_eq!(sequencer.sequences[1].repeat, 0);
+        assert!(history.redo(&mut sequencer));
+        assert_eq!(sequencer.sequences[1].repeat, 3);
+    }
+
+    #[test]
+    fn undo_of_a_removed_sequence_leaves_the_playlist() {
+        let mut sequencer = sequencer_with(2);
+        let mut history = History::new();
+        history.record(EditCommand::sequence(&sequencer, 1));
+        sequencer.sequences[0].repeat = 3;
+        sequencer.remove_sequence(1);
+
+        history.undo(&mut sequencer);
+        // The saved sequence 1 doesn't come back in place of the remaining one
+        assert_eq!(sequencer.sequences.len(), 1);
+        assert_eq!(sequencer.sequences[0].repeat, 3);
+        assert!(!history.redo(&mut sequencer));
+        assert_eq!(sequencer.sequences[0].repeat, 3);
+    }
+
+    #[test]
+    fn edits_made_before_a_reload_are_forgotten() {
+        let mut sequencer = sequencer_with(3);
+        let mut history = History::new();
+        sequencer.started = true;
+        let reloaded = Reload::Files {
+            scales: sequencer.scales.clone(),
+            sequences: vec![sequencer.sequences[0].clone()],
+        };
+        sequencer.reload(reloaded);
+        // Made while the reload waits for the end of the loop
+        history.record(EditCommand::sequence(&sequencer, 2));
+        sequencer.sequences[2].repeat = 3;
+
+        sequencer.next_note();
+        assert_eq!(sequencer.sequences.len(), 1);
+        assert!(!history.undo(&mut sequencer));
+        assert!(!history.redo(&mut sequencer));
+        assert_eq!(sequencer.sequences.len(), 1);
+    }
+
+    #[test]
+    fn edits_made_after_a_reload_can_be_undone() {
+        let mut sequencer = sequencer_with(2);
+        let mut history = History::new();
+        history.record(EditCommand::sequence(&sequencer, 1));
+        let reloaded = Reload::Files {
+            scales: sequencer.scales.clone(),
+            sequences: sequencer.sequences.clone(),
+        };
+        sequencer.reload(reloaded);
+        history.record(EditCommand::sequence(&sequencer, 0));
+        sequencer.sequences[0].repeat = 2;
+
+        assert!(history.undo(&mut sequencer));
+        assert_eq!(sequencer.sequences[0].repeat, 0);
+        assert!(!history.undo(&mut sequencer));
+    }
+}
@@ -0,0 +1,45 @@
+use crate::note::Note;
+use crate::random::Rng;
+
+/// Random deviations applied to the notes of a sequence when they are played.
+///
+/// - `velocity` is the largest change of velocity, up or down.
+/// - `timing` is the largest timing offset, as a fraction of a clock tick.
+/// - `pitch_cents` is the largest pitch drift.
+/// - `seed` makes the deviations repeatable: the same seed gives the same deviations every
+///   time the sequence starts.
+#[derive(Clone, Copy, Default)]
+pub(crate) struct Humanize {
+    pub(crate) velocity: u8,
+    pub(crate) timing: f64,
+    pub(crate) pitch_cents: f64,
+    pub(crate) seed: Option<u64>,
+}
+
+impl Humanize {
+    pub(crate) fn rng(&self) -> Rng {
+        match self.seed {
+            Some(seed) => Rng::new(seed),
+            None => Rng::from_time(),
+        }
+    }
+
+    pub(crate) fn is_active(&self) -> bool {
+        self.velocity > 0 || self.timing > 0f64 || self.pitch_cents > 0f64
+    }
+
+    /// Applies the deviations to a copy of a note about to be played.
+    pub(crate) fn apply(&self, note: &mut Note, rng: &mut Rng) {
+        if self.velocity > 0 {
+            let velocity = note.velocity as f64 + rng.deviation(self.velocity as f64);
+            note.velocity = velocity.round().clamp(1f64, 127f64) as u8;
+        }
+        if self.timing > 0f64 {
+            let microtiming = note.microtiming + rng.deviation(self.timing);
+            note.microtiming = microtiming.clamp(-0.99, 0.99);
+        }
+        if self.pitch_cents > 0f64 {
+            note.modulation.detune_cents += rng.deviation(self.pitch_cents);
+        }
+    }
+}
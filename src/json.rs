@@ -1,50 +1,420 @@
-use crate::note::{Note, NoteDuration, Scale};
+use crate::arpeggiator::{ArpMode, ARP_RATES};
+use crate::clock::{SyncMode, Tempo};
+use crate::generator::{Generator, MelodyConstraints, MelodyModel};
+use crate::humanize::Humanize;
+use crate::midi::MidiRouting;
+use crate::modulation::{Modulation, PitchEnvelope, Vibrato};
+use crate::note::{ControlChange, Gate, Glide, GlideCurve, Note, NoteDuration, Scale};
+use crate::output::{OutputMode, VelocityCurve};
+use crate::paths::DataSource;
 use crate::sequencer::{Sequence, Sequencer};
-use serde::de::Error;
-use serde::Deserialize;
-use serde_json;
+use crate::validate::{check, validate_scales, validate_sequences, Validation};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
 use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+use std::fs;
 use std::fs::File;
-use std::io::BufReader;
+use std::io;
+use std::io::{BufWriter, Write};
+use std::path::Path;
 use std::sync::{Arc};
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, Serialize)]
 pub struct JsonScale {
     pub name: String,
     pub steps: Vec<f64>,
     pub note_names: Vec<String>,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, Serialize)]
 pub struct JsonScaleFile {
     pub scales: Vec<JsonScale>,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, Serialize)]
 pub struct JsonSequenceFile {
     pub sequences: Vec<JsonSequence>,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, Serialize)]
 pub struct JsonNote {
     pub note_index: usize,
     octave: u8,
     duration: f64,
     velocity: u8,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    panning: Option<u8>,
+    #[serde(default, skip_serializing_if = "is_zero")]
+    microtiming: f64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    gate: Option<JsonGate>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    cc: Vec<JsonControlChange>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    program: Option<u8>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    aftertouch: Option<u8>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    glide: Option<JsonGlide>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    vibrato: Option<JsonVibrato>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pitch_envelope: Option<JsonPitchEnvelope>,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, Serialize)]
+pub struct JsonControlChange {
+    pub controller: u8,
+    pub value: u8,
+}
+
+#[derive(Debug, Default, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum JsonGlideCurve {
+    #[default]
+    Linear,
+    Exponential,
+    SCurve,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct JsonGlide {
+    pub time: f64,
+    #[serde(default)]
+    pub curve: JsonGlideCurve,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct JsonVibrato {
+    pub depth_cents: f64,
+    pub rate_hz: f64,
+    #[serde(default)]
+    pub delay_ms: f64,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct JsonPitchEnvelope {
+    pub start_cents: f64,
+    pub time_ms: f64,
+}
+
+/// Humanization of a sequence. `timing` is a fraction of a clock tick.
+#[derive(Debug, Deserialize, Serialize)]
+pub struct JsonHumanize {
+    #[serde(default)]
+    pub velocity: u8,
+    #[serde(default)]
+    pub timing: f64,
+    #[serde(default)]
+    pub pitch_cents: f64,
+    #[serde(default)]
+    pub seed: Option<u64>,
+}
+
+/// Generator of a sequence. Without `intervals`, the random walk uses a default table of
+/// mostly small steps. The Markov model is trained from the sequences of the file.
+#[derive(Debug, Deserialize, Serialize)]
+pub struct JsonGenerator {
+    #[serde(default)]
+    pub model: JsonMelodyModel,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub intervals: Vec<(i32, u32)>,
+    pub lowest: JsonDegree,
+    pub highest: JsonDegree,
+    pub max_leap: u32,
+    #[serde(default)]
+    pub tonic_gravity: f64,
+    #[serde(default)]
+    pub regenerate_every: u32,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub seed: Option<u64>,
+}
+
+#[derive(Debug, Default, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum JsonMelodyModel {
+    #[default]
+    RandomWalk,
+    Markov,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct JsonDegree {
+    pub note_index: usize,
+    pub octave: u8,
+}
+
+/// Gate of a note, written `{ "percent": 50 }` or `{ "beats": 0.25 }`.
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum JsonGate {
+    Percent(f64),
+    Beats(f64),
+}
+
+#[derive(Debug, Deserialize, Serialize)]
 pub struct JsonSequence {
     pub name: String,
     pub scale: String,
     pub repeat: u8,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub humanize: Option<JsonHumanize>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub generator: Option<JsonGenerator>,
     pub notes: Vec<JsonNote>,
 }
 
-fn read_scale_file(path: &str) -> serde_json::Result<JsonScaleFile> {
-    let file = File::open(path).map_err(serde_json::Error::io)?;
-    let reader = BufReader::new(file);
-    serde_json::from_reader(reader)
+/// Version of the project files written by this version of the sequencer.
+pub const PROJECT_VERSION: u32 = 1;
+
+/// Upgrades of the project files, the one at index `n` going from version `n + 1` to `n + 2`.
+const MIGRATIONS: [fn(&mut Value); PROJECT_VERSION as usize - 1] = [];
+
+/// A whole session in one file: the scales, the sequences in playlist order and the settings.
+///
+/// Files of older versions are upgraded when read. A file without a version, holding the
+/// content of a scale file and of a sequence file put together, is read as version 1.
+#[derive(Debug, Deserialize, Serialize)]
+pub struct JsonProjectFile {
+    pub version: u32,
+    pub scales: Vec<JsonScale>,
+    pub sequences: Vec<JsonSequence>,
+    #[serde(default)]
+    pub midi: JsonMidiRouting,
+    #[serde(default)]
+    pub tempo: JsonTempo,
+    #[serde(default)]
+    pub settings: JsonSettings,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(default)]
+pub struct JsonMidiRouting {
+    pub input_port: String,
+    pub output_port: String,
+}
+
+impl Default for JsonMidiRouting {
+    fn default() -> Self {
+        let routing = MidiRouting::new();
+        Self {
+            input_port: routing.input_port,
+            output_port: routing.output_port,
+        }
+    }
+}
+
+#[derive(Debug, Default, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum JsonSyncMode {
+    #[default]
+    External,
+    Internal,
+}
+
+/// Clock settings. `bpm` is only used by the internal clock.
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(default)]
+pub struct JsonTempo {
+    pub sync: JsonSyncMode,
+    pub bpm: f64,
+}
+
+impl Default for JsonTempo {
+    fn default() -> Self {
+        Self {
+            sync: JsonSyncMode::External,
+            bpm: Tempo::new().bpm,
+        }
+    }
+}
+
+/// The settings of the settings menu. `arpeggiator_rate` is one of the rates of the menu,
+/// like `"1/16"`.
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(default)]
+pub struct JsonSettings {
+    pub arpeggiator: JsonArpMode,
+    pub arpeggiator_rate: String,
+    pub keyboard_transpose: bool,
+    pub transpose_root: u8,
+    pub output: JsonOutputMode,
+    pub velocity_scale: u8,
+    pub velocity_curve: JsonVelocityCurve,
+    pub pitch_update_rate: u32,
+}
+
+impl Default for JsonSettings {
+    fn default() -> Self {
+        build_json_settings_from_sequencer(&Sequencer::new(vec![], vec![]))
+    }
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum JsonArpMode {
+    Off,
+    Up,
+    Down,
+    UpDown,
+    Random,
+    AsPlayed,
+}
+
+/// Output mode, written `"mono"` or `{ "poly": { "channels": 4 } }`.
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum JsonOutputMode {
+    Mono,
+    Poly { channels: u8 },
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum JsonVelocityCurve {
+    Linear,
+    Exponential,
+    Fixed,
+}
+
+/// A scale, sequence or project file, told apart by their top-level fields.
+#[derive(Debug)]
+pub enum JsonDataFile {
+    Scales(JsonScaleFile),
+    Sequences(JsonSequenceFile),
+    Project(JsonProjectFile),
+}
+
+/// Why a scale or sequence file could not be loaded.
+#[derive(Debug)]
+pub enum LoadError {
+    /// The file can't be opened or read, for example because it doesn't exist.
+    Io { path: String, source: io::Error },
+    /// The file isn't valid JSON, or doesn't have the fields of a scale or sequence file.
+    Syntax {
+        path: String,
+        line: usize,
+        column: usize,
+        message: String,
+    },
+    /// A sequence refers to a scale that isn't loaded.
+    UnknownScale {
+        path: String,
+        sequence: String,
+        scale: String,
+    },
+    /// A note refers to a degree past the end of its scale.
+    NoteIndexOutOfRange {
+        path: String,
+        sequence: String,
+        note: usize,
+        note_index: usize,
+        scale_len: usize,
+    },
+    /// The validation found errors, like a sequence without notes.
+    Invalid { path: String, validation: Validation },
+    /// The file is valid JSON, but not the kind of file expected.
+    UnexpectedFormat {
+        path: String,
+        expected: &'static str,
+    },
+    /// The project file was written by a newer version of the sequencer.
+    UnsupportedVersion { path: String, version: u64 },
+}
+
+impl LoadError {
+    fn from_json_error(path: &str, err: serde_json::Error) -> Self {
+        if err.is_io() {
+            return LoadError::Io {
+                path: path.to_string(),
+                source: err.into(),
+            };
+        }
+        // The message of serde_json ends with the position, which is kept apart
+        let message = err.to_string();
+        let message = match message.rsplit_once(" at line ") {
+            Some((message, _)) => message.to_string(),
+            None => message,
+        };
+        LoadError::Syntax {
+            path: path.to_string(),
+            line: err.line(),
+            column: err.column(),
+            message,
+        }
+    }
+}
+
+impl fmt::Display for LoadError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LoadError::Io { path, source } => write!(f, "Can't read {}: {}", path, source),
+            LoadError::Syntax {
+                path,
+                line,
+                column,
+                message,
+            } => write!(f, "{}, line {}, column {}: {}", path, line, column, message),
+            LoadError::UnknownScale {
+                path,
+                sequence,
+                scale,
+            } => write!(
+                f,
+                "{}: sequence {} uses the scale {}, which isn't loaded",
+                path, sequence, scale
+            ),
+            LoadError::NoteIndexOutOfRange {
+                path,
+                sequence,
+                note,
+                note_index,
+                scale_len,
+            } => write!(
+                f,
+                "{}: note {} of sequence {} has the note_index {}, but its scale has {} degrees",
+                path, note, sequence, note_index, scale_len
+            ),
+            LoadError::Invalid { path, validation } => {
+                write!(f, "{} can't be loaded:\n{}", path, validation)
+            }
+            LoadError::UnexpectedFormat { path, expected } => {
+                write!(f, "{} isn't a {} file", path, expected)
+            }
+            LoadError::UnsupportedVersion { path, version } => write!(
+                f,
+                "{} is a version {} project, but only versions 1 to {} can be loaded",
+                path, version, PROJECT_VERSION
+            ),
+        }
+    }
+}
+
+impl Error for LoadError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            LoadError::Io { source, .. } => Some(source),
+            _ => None,
+        }
+    }
+}
+
+impl From<LoadError> for io::Error {
+    fn from(err: LoadError) -> Self {
+        io::Error::other(err)
+    }
+}
+
+fn is_zero(value: &f64) -> bool {
+    *value == 0f64
+}
+
+/// Parses the text of a file. `path` only names the file in errors.
+fn parse_json<T: DeserializeOwned>(path: &str, text: &str) -> Result<T, LoadError> {
+    serde_json::from_str(text).map_err(|err| LoadError::from_json_error(path, err))
 }
 
 fn build_scale_from_json_scale(json_scale: JsonScale) -> Scale {
@@ -55,23 +425,58 @@ fn build_scale_from_json_scale(json_scale: JsonScale) -> Scale {
     }
 }
 
-pub fn get_scales_from_json_file(path: &str) -> serde_json::Result<Vec<Scale>> {
-    let file = match read_scale_file(path) {
-        Ok(f) => f,
-        Err(e) => return Err(e),
-    };
-
-    Ok(file
-        .scales
+pub fn build_scales_from_json_scale_file(file: JsonScaleFile) -> Vec<Scale> {
+    file.scales
         .into_iter()
         .map(build_scale_from_json_scale)
-        .collect())
+        .collect()
 }
 
-fn read_sequence_file(path: &str) -> serde_json::Result<JsonSequenceFile> {
-    let file = File::open(path).map_err(serde_json::Error::io)?;
-    let reader = BufReader::new(file);
-    serde_json::from_reader(reader)
+/// Reads a file that may hold scales, sequences or a whole project. Projects of older
+/// versions are upgraded.
+pub fn read_data_file(path: &str) -> Result<JsonDataFile, LoadError> {
+    let text = fs::read_to_string(path).map_err(|source| LoadError::Io {
+        path: path.to_string(),
+        source,
+    })?;
+    let value: Value = parse_json(path, &text)?;
+    let has = |field: &str| value.get(field).is_some();
+    if has("version") || (has("scales") && has("sequences")) {
+        read_project(path, &text, value).map(JsonDataFile::Project)
+    } else if has("scales") {
+        parse_json(path, &text).map(JsonDataFile::Scales)
+    } else if has("sequences") {
+        parse_json(path, &text).map(JsonDataFile::Sequences)
+    } else {
+        Err(LoadError::UnexpectedFormat {
+            path: path.to_string(),
+            expected: "scale, sequence or project",
+        })
+    }
+}
+
+/// Parses a project file, upgrading it first if it is of an older version.
+fn read_project(path: &str, text: &str, mut project: Value) -> Result<JsonProjectFile, LoadError> {
+    // A version that isn't a number is reported by the parsing of the current version
+    let version = match project.get("version") {
+        None => 1,
+        Some(version) => version.as_u64().unwrap_or(PROJECT_VERSION as u64),
+    };
+    if version == 0 || version > PROJECT_VERSION as u64 {
+        return Err(LoadError::UnsupportedVersion {
+            path: path.to_string(),
+            version,
+        });
+    }
+    if version == PROJECT_VERSION as u64 && project.get("version").is_some() {
+        // Parsed from the text rather than the value, to keep the position of the errors
+        return parse_json(path, text);
+    }
+    for migrate in MIGRATIONS.iter().skip(version as usize - 1) {
+        migrate(&mut project);
+    }
+    project["version"] = Value::from(PROJECT_VERSION);
+    serde_json::from_value(project).map_err(|err| LoadError::from_json_error(path, err))
 }
 
 fn build_note_from_json_note(json_note: JsonNote, scale: Arc<Scale>) -> Note {
@@ -83,60 +488,561 @@ fn build_note_from_json_note(json_note: JsonNote, scale: Arc<Scale>) -> Note {
         },
         note_index: json_note.note_index,
         velocity: json_note.velocity,
+        panning: json_note.panning,
+        microtiming: json_note.microtiming,
+        gate: match json_note.gate {
+            None => Gate::full(),
+            Some(JsonGate::Percent(percent)) => Gate::Percent(percent),
+            Some(JsonGate::Beats(beats)) => Gate::Beats(beats),
+        },
+        control_changes: json_note
+            .cc
+            .iter()
+            .map(|cc| ControlChange {
+                controller: cc.controller,
+                value: cc.value,
+            })
+            .collect(),
+        program: json_note.program,
+        aftertouch: json_note.aftertouch,
+        glide: json_note.glide.map(|glide| Glide {
+            time: glide.time,
+            curve: match glide.curve {
+                JsonGlideCurve::Linear => GlideCurve::Linear,
+                JsonGlideCurve::Exponential => GlideCurve::Exponential,
+                JsonGlideCurve::SCurve => GlideCurve::SCurve,
+            },
+        }),
+        modulation: Modulation {
+            vibrato: json_note.vibrato.map(|vibrato| Vibrato {
+                depth_cents: vibrato.depth_cents,
+                rate_hz: vibrato.rate_hz,
+                delay_ms: vibrato.delay_ms,
+            }),
+            envelope: json_note.pitch_envelope.map(|envelope| PitchEnvelope {
+                start_cents: envelope.start_cents,
+                time_ms: envelope.time_ms,
+            }),
+            detune_cents: 0f64,
+        },
     }
 }
 
 fn build_sequence_from_json_sequence(
+    path: &str,
     json_sequence: JsonSequence,
     scale: Arc<Scale>,
-) -> serde_json::Result<Sequence> {
+) -> Result<Sequence, LoadError> {
+    let scale_len = scale.steps.len();
+    if let Some((note, json_note)) = json_sequence
+        .notes
+        .iter()
+        .enumerate()
+        .find(|(_, n)| n.note_index >= scale_len)
+    {
+        return Err(LoadError::NoteIndexOutOfRange {
+            path: path.to_string(),
+            sequence: json_sequence.name.clone(),
+            note,
+            note_index: json_note.note_index,
+            scale_len,
+        });
+    }
+
     let notes = json_sequence
         .notes
         .into_iter()
         .map(|n| build_note_from_json_note(n, scale.clone()))
         .collect();
 
-    Ok(Sequence::new(json_sequence.name, scale.clone(), notes))
+    let mut sequence = Sequence::new(json_sequence.name, scale.clone(), notes);
+    sequence.repeat = json_sequence.repeat as usize;
+    if let Some(humanize) = json_sequence.humanize {
+        sequence.humanize = Humanize {
+            velocity: humanize.velocity,
+            timing: humanize.timing,
+            pitch_cents: humanize.pitch_cents,
+            seed: humanize.seed,
+        };
+    }
+    if let Some(generator) = json_sequence.generator {
+        sequence.generator = Some(build_generator_from_json_generator(generator, &scale));
+    }
+    Ok(sequence)
+}
+
+fn build_generator_from_json_generator(json_generator: JsonGenerator, scale: &Scale) -> Generator {
+    let scale_len = scale.steps.len() as i32;
+    let degree = |d: &JsonDegree| d.octave as i32 * scale_len + d.note_index as i32;
+    let model = match json_generator.model {
+        JsonMelodyModel::RandomWalk => MelodyModel::random_walk(json_generator.intervals),
+        // Trained once all the sequences of the file are built
+        JsonMelodyModel::Markov => MelodyModel::markov(scale, &[]),
+    };
+    Generator::new(
+        model,
+        MelodyConstraints {
+            lowest: degree(&json_generator.lowest),
+            highest: degree(&json_generator.highest),
+            max_leap: json_generator.max_leap,
+            tonic_gravity: json_generator.tonic_gravity,
+        },
+        json_generator.regenerate_every,
+        json_generator.seed,
+    )
 }
 
 fn get_arc_scale_hashmap_from_json_sequences(
+    path: &str,
     sequences: &Vec<JsonSequence>,
-    scales: Vec<Scale>,
-) -> HashMap<String, Arc<Scale>> {
+    scales: &[Arc<Scale>],
+) -> Result<HashMap<String, Arc<Scale>>, LoadError> {
     let mut scales_hashmap: HashMap<String, Arc<Scale>> = HashMap::new();
-    let scales: Vec<Arc<Scale>> = scales.into_iter().map(Arc::new).collect();
 
     for seq in sequences {
-        scales_hashmap
-            .entry(seq.scale.clone())
-            .or_insert_with(|| Arc::clone(scales.iter().find(|s| s.name == seq.scale).unwrap()));
+        if scales_hashmap.contains_key(&seq.scale) {
+            continue;
+        }
+        let scale = scales.iter().find(|s| s.name == seq.scale).ok_or_else(|| {
+            LoadError::UnknownScale {
+                path: path.to_string(),
+                sequence: seq.name.clone(),
+                scale: seq.scale.clone(),
+            }
+        })?;
+        scales_hashmap.insert(seq.scale.clone(), Arc::clone(scale));
     }
 
-    scales_hashmap
+    Ok(scales_hashmap)
 }
 
-pub fn get_sequences_from_json_file(
+/// Builds the sequences of a file read from `path`, which only names the file in errors.
+pub fn build_sequences_from_json_sequence_file(
     path: &str,
-    scales: Vec<Scale>,
-) -> serde_json::Result<Vec<Sequence>> {
-    let file = match read_sequence_file(path) {
-        Ok(f) => f,
-        Err(e) => return Err(e),
-    };
+    file: JsonSequenceFile,
+    scales: &[Arc<Scale>],
+) -> Result<Vec<Sequence>, LoadError> {
+    let scales = get_arc_scale_hashmap_from_json_sequences(path, &file.sequences, scales)?;
 
-    let scales = get_arc_scale_hashmap_from_json_sequences(&file.sequences, scales);
-    
     let mut sequences = vec![];
     for seq in file.sequences {
         let scale = scales[&seq.scale].clone();
-        sequences.push(build_sequence_from_json_sequence(seq, scale)?);
+        sequences.push(build_sequence_from_json_sequence(path, seq, scale)?);
+    }
+
+    let melodies: Vec<Vec<Note>> = sequences.iter().map(|s| s.notes.clone()).collect();
+    let melodies: Vec<&[Note]> = melodies.iter().map(|notes| notes.as_slice()).collect();
+    for sequence in &mut sequences {
+        sequence.train_generator(&melodies);
     }
 
     Ok(sequences)
 }
 
-pub fn get_sequencer_from_json(scale_path: &str, sequence_path: &str) -> Sequencer {
-    let scales = get_scales_from_json_file(scale_path).unwrap();
-    let sequences = get_sequences_from_json_file(sequence_path, scales).unwrap();
-    Sequencer::new(sequences)
+/// Builds and validates the sequencer of a project read from `path`. Returns the warnings of
+/// the validation with it.
+pub fn build_sequencer_from_json_project_file(
+    path: &str,
+    file: JsonProjectFile,
+) -> Result<(Sequencer, Validation), LoadError> {
+    let scales: Vec<Arc<Scale>> = file
+        .scales
+        .into_iter()
+        .map(|scale| Arc::new(build_scale_from_json_scale(scale)))
+        .collect();
+    let mut warnings = check(path, validate_scales(&scales))?;
+    let sequences = build_sequences_from_json_sequence_file(
+        path,
+        JsonSequenceFile {
+            sequences: file.sequences,
+        },
+        &scales,
+    )?;
+    let sequence_warnings = check(path, validate_sequences(&sequences))?;
+    warnings.issues.extend(sequence_warnings.issues);
+
+    let mut sequencer = Sequencer::new(sequences, scales);
+    sequencer.routing = MidiRouting {
+        input_port: file.midi.input_port,
+        output_port: file.midi.output_port,
+    };
+    sequencer.tempo = Tempo {
+        sync: match file.tempo.sync {
+            JsonSyncMode::External => SyncMode::External,
+            JsonSyncMode::Internal => SyncMode::Internal,
+        },
+        bpm: file.tempo.bpm.clamp(Tempo::MIN_BPM, Tempo::MAX_BPM),
+    };
+    apply_json_settings(file.settings, &mut sequencer);
+    Ok((sequencer, warnings))
+}
+
+/// An unknown arpeggiator rate leaves the default one.
+fn apply_json_settings(settings: JsonSettings, sequencer: &mut Sequencer) {
+    sequencer.arpeggiator.mode = match settings.arpeggiator {
+        JsonArpMode::Off => ArpMode::Off,
+        JsonArpMode::Up => ArpMode::Up,
+        JsonArpMode::Down => ArpMode::Down,
+        JsonArpMode::UpDown => ArpMode::UpDown,
+        JsonArpMode::Random => ArpMode::Random,
+        JsonArpMode::AsPlayed => ArpMode::AsPlayed,
+    };
+    if let Some(i) = ARP_RATES
+        .iter()
+        .position(|(name, _)| *name == settings.arpeggiator_rate)
+    {
+        sequencer.arpeggiator.rate_index = i;
+    }
+    sequencer.transpose.enabled = settings.keyboard_transpose;
+    sequencer.transpose.root = settings.transpose_root.min(127);
+    sequencer.output.mode = match settings.output {
+        JsonOutputMode::Mono => OutputMode::Mono,
+        JsonOutputMode::Poly { channels } => OutputMode::Poly {
+            channels: channels.clamp(2, 16),
+        },
+    };
+    sequencer.output.velocity_scale = settings.velocity_scale.min(200);
+    sequencer.output.velocity_curve = match settings.velocity_curve {
+        JsonVelocityCurve::Linear => VelocityCurve::Linear,
+        JsonVelocityCurve::Exponential => VelocityCurve::Exponential,
+        JsonVelocityCurve::Fixed => VelocityCurve::Fixed,
+    };
+    sequencer.pitch_update_rate = settings.pitch_update_rate.max(1);
+}
+
+/// Loads and validates the scale and sequence files. Returns the warnings of the validation
+/// with the sequencer.
+pub(crate) fn get_sequencer_from_json(
+    scale_source: &DataSource,
+    sequence_source: &DataSource,
+) -> Result<(Sequencer, Validation), LoadError> {
+    let scale_path = &scale_source.name();
+    let file: JsonScaleFile = parse_json(scale_path, &scale_source.read()?)?;
+    let scales: Vec<Arc<Scale>> = build_scales_from_json_scale_file(file)
+        .into_iter()
+        .map(Arc::new)
+        .collect();
+    let mut warnings = check(scale_path, validate_scales(&scales))?;
+    let sequence_path = &sequence_source.name();
+    let file: JsonSequenceFile = parse_json(sequence_path, &sequence_source.read()?)?;
+    let sequences = build_sequences_from_json_sequence_file(sequence_path, file, &scales)?;
+    let sequence_warnings = check(sequence_path, validate_sequences(&sequences))?;
+    warnings.issues.extend(sequence_warnings.issues);
+    Ok((Sequencer::new(sequences, scales), warnings))
+}
+
+/// Loads and validates a project file. Returns the warnings of the validation with the
+/// sequencer.
+pub fn get_sequencer_from_project_file(path: &str) -> Result<(Sequencer, Validation), LoadError> {
+    match read_data_file(path)? {
+        JsonDataFile::Project(file) => build_sequencer_from_json_project_file(path, file),
+        _ => Err(LoadError::UnexpectedFormat {
+            path: path.to_string(),
+            expected: "project",
+        }),
+    }
+}
+
+fn build_json_note_from_note(note: &Note) -> JsonNote {
+    JsonNote {
+        note_index: note.note_index,
+        octave: note.octave,
+        duration: note.duration.duration,
+        velocity: note.velocity,
+        panning: note.panning,
+        microtiming: note.microtiming,
+        gate: match note.gate {
+            Gate::Percent(100f64) => None,
+            Gate::Percent(percent) => Some(JsonGate::Percent(percent)),
+            Gate::Beats(beats) => Some(JsonGate::Beats(beats)),
+        },
+        cc: note
+            .control_changes
+            .iter()
+            .map(|cc| JsonControlChange {
+                controller: cc.controller,
+                value: cc.value,
+            })
+            .collect(),
+        program: note.program,
+        aftertouch: note.aftertouch,
+        glide: note.glide.as_ref().map(|glide| JsonGlide {
+            time: glide.time,
+            curve: match glide.curve {
+                GlideCurve::Linear => JsonGlideCurve::Linear,
+                GlideCurve::Exponential => JsonGlideCurve::Exponential,
+                GlideCurve::SCurve => JsonGlideCurve::SCurve,
+            },
+        }),
+        vibrato: note.modulation.vibrato.map(|vibrato| JsonVibrato {
+            depth_cents: vibrato.depth_cents,
+            rate_hz: vibrato.rate_hz,
+            delay_ms: vibrato.delay_ms,
+        }),
+        pitch_envelope: note.modulation.envelope.map(|envelope| JsonPitchEnvelope {
+            start_cents: envelope.start_cents,
+            time_ms: envelope.time_ms,
+        }),
+    }
+}
+
+fn build_json_generator_from_generator(generator: &Generator, scale: &Scale) -> JsonGenerator {
+    let scale_len = scale.steps.len() as i32;
+    let degree = |d: i32| JsonDegree {
+        note_index: d.rem_euclid(scale_len) as usize,
+        octave: d.div_euclid(scale_len).clamp(0, u8::MAX as i32) as u8,
+    };
+    let (model, intervals) = match &generator.model {
+        MelodyModel::RandomWalk { intervals } => (JsonMelodyModel::RandomWalk, intervals.clone()),
+        MelodyModel::Markov { .. } => (JsonMelodyModel::Markov, vec![]),
+    };
+    JsonGenerator {
+        model,
+        intervals,
+        lowest: degree(generator.constraints.lowest),
+        highest: degree(generator.constraints.highest),
+        max_leap: generator.constraints.max_leap,
+        tonic_gravity: generator.constraints.tonic_gravity,
+        regenerate_every: generator.regenerate_every,
+        seed: generator.seed,
+    }
+}
+
+fn build_json_sequence_from_sequence(sequence: &Sequence) -> JsonSequence {
+    let scale = sequence.scale();
+    let humanize = sequence.humanize;
+    JsonSequence {
+        name: sequence.name.clone(),
+        scale: scale.name.clone(),
+        repeat: sequence.repeat.min(u8::MAX as usize) as u8,
+        humanize: match humanize.is_active() {
+            true => Some(JsonHumanize {
+                velocity: humanize.velocity,
+                timing: humanize.timing,
+                pitch_cents: humanize.pitch_cents,
+                seed: humanize.seed,
+            }),
+            false => None,
+        },
+        generator: sequence
+            .generator
+            .as_ref()
+            .map(|generator| build_json_generator_from_generator(generator, &scale)),
+        notes: sequence.notes.iter().map(build_json_note_from_note).collect(),
+    }
+}
+
+/// Writes the file next to `path` first and renames it over `path` once complete, so that a
+/// crash while writing leaves the previous file untouched.
+fn write_json_file<T: Serialize>(path: &str, value: &T) -> io::Result<()> {
+    if let Some(parent) = Path::new(path).parent().filter(|p| !p.as_os_str().is_empty()) {
+        fs::create_dir_all(parent)?;
+    }
+    let temp_path = format!("{}.tmp", path);
+    let written = write_json_file_contents(&temp_path, value);
+    if written.is_err() {
+        let _ = fs::remove_file(&temp_path);
+        return written;
+    }
+    fs::rename(&temp_path, path)
+}
+
+fn write_json_file_contents<T: Serialize>(path: &str, value: &T) -> io::Result<()> {
+    let mut writer = BufWriter::new(File::create(path)?);
+    serde_json::to_writer_pretty(&mut writer, value)?;
+    writer.flush()?;
+    writer.get_ref().sync_all()
+}
+
+/// Writes the sequences in the format of `sequences.json`, notes as they are now, including
+/// the generated melodies.
+pub fn save_sequences_to_json_file(path: &str, sequences: &[Sequence]) -> io::Result<()> {
+    let file = JsonSequenceFile {
+        sequences: sequences.iter().map(build_json_sequence_from_sequence).collect(),
+    };
+    write_json_file(path, &file)
+}
+
+pub fn save_scales_to_json_file(path: &str, scales: &[Arc<Scale>]) -> io::Result<()> {
+    let file = JsonScaleFile {
+        scales: scales.iter().map(|scale| build_json_scale_from_scale(scale)).collect(),
+    };
+    write_json_file(path, &file)
+}
+
+/// Writes the scales, sequences and settings of the sequencer to a project file.
+pub fn save_project_to_json_file(path: &str, sequencer: &Sequencer) -> io::Result<()> {
+    let file = JsonProjectFile {
+        version: PROJECT_VERSION,
+        scales: sequencer.scales.iter().map(|scale| build_json_scale_from_scale(scale)).collect(),
+        sequences: sequencer
+            .sequences
+            .iter()
+            .map(build_json_sequence_from_sequence)
+            .collect(),
+        midi: JsonMidiRouting {
+            input_port: sequencer.routing.input_port.clone(),
+            output_port: sequencer.routing.output_port.clone(),
+        },
+        tempo: JsonTempo {
+            sync: match sequencer.tempo.sync {
+                SyncMode::External => JsonSyncMode::External,
+                SyncMode::Internal => JsonSyncMode::Internal,
+            },
+            bpm: sequencer.tempo.bpm,
+        },
+        settings: build_json_settings_from_sequencer(sequencer),
+    };
+    write_json_file(path, &file)
+}
+
+fn build_json_scale_from_scale(scale: &Scale) -> JsonScale {
+    JsonScale {
+        name: scale.name.clone(),
+        steps: scale.steps.clone(),
+        note_names: scale.note_names.clone(),
+    }
+}
+
+fn build_json_settings_from_sequencer(sequencer: &Sequencer) -> JsonSettings {
+    JsonSettings {
+        arpeggiator: match sequencer.arpeggiator.mode {
+            ArpMode::Off => JsonArpMode::Off,
+            ArpMode::Up => JsonArpMode::Up,
+            ArpMode::Down => JsonArpMode::Down,
+            ArpMode::UpDown => JsonArpMode::UpDown,
+            ArpMode::Random => JsonArpMode::Random,
+            ArpMode::AsPlayed => JsonArpMode::AsPlayed,
+        },
+        arpeggiator_rate: sequencer.arpeggiator.rate_name().to_string(),
+        keyboard_transpose: sequencer.transpose.enabled,
+        transpose_root: sequencer.transpose.root,
+        output: match sequencer.output.mode {
+            OutputMode::Mono => JsonOutputMode::Mono,
+            OutputMode::Poly { channels } => JsonOutputMode::Poly { channels },
+        },
+        velocity_scale: sequencer.output.velocity_scale,
+        velocity_curve: match sequencer.output.velocity_curve {
+            VelocityCurve::Linear => JsonVelocityCurve::Linear,
+            VelocityCurve::Exponential => JsonVelocityCurve::Exponential,
+            VelocityCurve::Fixed => JsonVelocityCurve::Fixed,
+        },
+        pitch_update_rate: sequencer.pitch_update_rate,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::paths::DataSource;
+    use std::path::PathBuf;
+
+    const SCALES: &str = r#"{"scales": [
+        {"name": "12-TET", "steps": [0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11],
+         "note_names": ["C", "C#", "D", "D#", "E", "F", "F#", "G", "G#", "A", "A#", "B"]}
+    ]}"#;
+    const SEQUENCES: &str = r#"{"sequences": [
+        {"name": "Intro", "scale": "12-TET", "repeat": 2, "notes": [
+            {"note_index": 0, "octave": 5, "duration": 1, "velocity": 100},
+            {"note_index": 7, "octave": 5, "duration": 0.5, "velocity": 90}
+        ]}
+    ]}"#;
+
+    /// A path in the temporary folder, unique to the test.
+    fn temp_path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("microtonal-sequencer-{}-{}", std::process::id(), name))
+    }
+
+    fn unversioned_project() -> String {
+        let scales: Value = serde_json::from_str(SCALES).unwrap();
+        let sequences: Value = serde_json::from_str(SEQUENCES).unwrap();
+        let project = serde_json::json!({
+            "scales": scales["scales"],
+            "sequences": sequences["sequences"],
+        });
+        project.to_string()
+    }
+
+    #[test]
+    fn every_older_version_has_a_migration() {
+        assert_eq!(MIGRATIONS.len(), PROJECT_VERSION as usize - 1);
+    }
+
+    #[test]
+    fn unversioned_projects_are_read_with_default_settings() {
+        let text = unversioned_project();
+        let value = serde_json::from_str(&text).unwrap();
+        let project = read_project("project.json", &text, value).unwrap();
+        assert_eq!(project.version, PROJECT_VERSION);
+        assert_eq!(project.midi.input_port, MidiRouting::new().input_port);
+
+        let (sequencer, warnings) =
+            build_sequencer_from_json_project_file("project.json", project).unwrap();
+        assert!(warnings.is_empty());
+        assert_eq!(sequencer.sequences[0].repeat, 2);
+        assert_eq!(sequencer.sequences[0].notes[1].note_index, 7);
+    }
+
+    #[test]
+    fn newer_versions_are_refused() {
+        let text = r#"{"version": 99, "scales": [], "sequences": []}"#;
+        let value = serde_json::from_str(text).unwrap();
+        let err = read_project("project.json", text, value).unwrap_err();
+        assert!(matches!(err, LoadError::UnsupportedVersion { version: 99, .. }));
+    }
+
+    #[test]
+    fn version_0_is_refused() {
+        let text = r#"{"version": 0, "scales": [], "sequences": []}"#;
+        let value = serde_json::from_str(text).unwrap();
+        let err = read_project("project.json", text, value).unwrap_err();
+        assert!(matches!(err, LoadError::UnsupportedVersion { version: 0, .. }));
+    }
+
+    #[test]
+    fn projects_keep_their_settings_when_saved_and_read() {
+        let scales = temp_path("scales.json");
+        let sequences = temp_path("sequences.json");
+        let project = temp_path("project.json").to_string_lossy().to_string();
+        fs::write(&scales, SCALES).unwrap();
+        fs::write(&sequences, SEQUENCES).unwrap();
+        let sources = (DataSource::File(scales.clone()), DataSource::File(sequences.clone()));
+        let (mut sequencer, _) = get_sequencer_from_json(&sources.0, &sources.1).unwrap();
+        sequencer.tempo.sync = SyncMode::Internal;
+        sequencer.tempo.bpm = 97f64;
+        sequencer.routing.output_port = "Synth".to_string();
+        sequencer.output.mode = OutputMode::Poly { channels: 4 };
+
+        save_project_to_json_file(&project, &sequencer).unwrap();
+        let (loaded, _) = get_sequencer_from_project_file(&project).unwrap();
+        assert!(loaded.tempo.sync == SyncMode::Internal);
+        assert_eq!(loaded.tempo.bpm, 97f64);
+        assert_eq!(loaded.routing.output_port, "Synth");
+        assert!(matches!(loaded.output.mode, OutputMode::Poly { channels: 4 }));
+        assert!(loaded.sequences[0].notes == sequencer.sequences[0].notes);
+        for path in [scales, sequences, PathBuf::from(project)] {
+            fs::remove_file(path).unwrap();
+        }
+    }
+
+    #[test]
+    fn data_files_are_told_apart() {
+        let scales = temp_path("told-apart-scales.json");
+        let project = temp_path("told-apart-project.json");
+        fs::write(&scales, SCALES).unwrap();
+        fs::write(&project, unversioned_project()).unwrap();
+        let read = |path: &PathBuf| read_data_file(&path.to_string_lossy()).unwrap();
+        assert!(matches!(read(&scales), JsonDataFile::Scales(_)));
+        assert!(matches!(read(&project), JsonDataFile::Project(_)));
+        fs::remove_file(scales).unwrap();
+        fs::remove_file(project).unwrap();
+    }
+
+    #[test]
+    fn unknown_scales_are_reported() {
+        let text = SEQUENCES.replace("\"scale\": \"12-TET\"", "\"scale\": \"19-TET\"");
+        let file: JsonSequenceFile = parse_json("sequences.json", &text).unwrap();
+        let err = build_sequences_from_json_sequence_file("sequences.json", file, &[]);
+        assert!(matches!(err, Err(LoadError::UnknownScale { .. })));
+    }
 }
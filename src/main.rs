@@ -1,30 +1,54 @@
 extern crate core;
 
+mod args;
+mod arpeggiator;
 mod clock;
+mod generator;
+mod history;
+mod humanize;
 mod json;
 mod midi;
+mod modulation;
 mod note;
+mod output;
+mod paths;
+mod random;
+mod scheduler;
 mod sequencer;
 mod tui;
+mod validate;
 
-use crate::json::get_sequencer_from_json;
-use crate::midi::{create_input_connection, create_output_connection, stop_sequencer};
+use crate::args::{parse_args, Args, USAGE};
+use crate::json::{get_sequencer_from_json, get_sequencer_from_project_file, LoadError};
+use crate::clock::SyncMode;
+use crate::midi::{
+    create_input_connection, create_output_connection, play_scheduled_notes,
+    start_internal_clock, stop_sequencer, update_pitch_bends,
+};
+use crate::paths::{DataPaths, DataSource};
 use crate::tui::run_tui;
+use crate::validate::Validation;
 use midir::{MidiInputConnection, MidiOutputConnection};
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::{Arc, Mutex};
-use std::time::Duration;
-
-const SCALE_PATH: &str = "data\\scales.json";
-const SEQUENCE_PATH: &str = "data\\sequences.json";
+use std::time::{Duration, Instant};
 
 fn init_sequencer(
     sequencer: Arc<Mutex<sequencer::Sequencer>>,
     on: Arc<AtomicBool>,
 ) -> Result<(), std::io::Error> {
     on.store(true, Ordering::SeqCst);
-    let output_conn = Arc::new(Mutex::new(create_output_connection()?));
-    let input_conn = create_input_connection(sequencer.clone(), output_conn.clone())?;
+    let (input_port, output_port, sync) = {
+        let sequencer = sequencer.lock().unwrap();
+        let routing = &sequencer.routing;
+        (routing.input_port.clone(), routing.output_port.clone(), sequencer.tempo.sync)
+    };
+    let output_conn = Arc::new(Mutex::new(create_output_connection(&output_port)?));
+    let input_conn =
+        create_input_connection(sequencer.clone(), output_conn.clone(), &input_port, sync)?;
+    if sync == SyncMode::Internal {
+        start_internal_clock(sequencer.clone(), output_conn.clone(), on.clone());
+    }
     std::thread::spawn(move || {
         start_main_loop(input_conn, on, output_conn.clone(), sequencer.clone());
     });
@@ -32,24 +56,111 @@ fn init_sequencer(
 }
 
 fn start_main_loop(_input_conn: MidiInputConnection<()>, on: Arc<AtomicBool>, output_conn: Arc<Mutex<MidiOutputConnection>>, sequencer: Arc<Mutex<sequencer::Sequencer>>) {
+    let mut last_pitch_update = Instant::now();
     while on.load(Ordering::SeqCst) {
-        std::thread::sleep(Duration::from_millis(50));
+        play_scheduled_notes(sequencer.clone(), output_conn.clone());
+
+        let pitch_update_rate = sequencer.lock().unwrap().pitch_update_rate;
+        if last_pitch_update.elapsed().as_secs_f64() * pitch_update_rate as f64 >= 1f64 {
+            update_pitch_bends(sequencer.clone(), output_conn.clone());
+            last_pitch_update = Instant::now();
+        }
+        std::thread::sleep(Duration::from_millis(1));
     }
-    stop_sequencer(&mut output_conn.lock().unwrap(), sequencer.clone())
+    stop_sequencer(sequencer, output_conn)
 }
 
+#[allow(dead_code)]
 fn run_debug(sequencer: Arc<Mutex<sequencer::Sequencer>>) {
     init_sequencer(sequencer, Arc::new(AtomicBool::new(true))).unwrap();
     loop {
+        std::thread::sleep(Duration::from_millis(50));
+    }
+}
 
+/// Loads the project given on the command line, or else the scale and sequence files.
+fn load(
+    args: &Args,
+    scales: &DataSource,
+    sequences: &DataSource,
+) -> Result<(sequencer::Sequencer, Validation), LoadError> {
+    match &args.project_path {
+        Some(path) => get_sequencer_from_project_file(path),
+        None => get_sequencer_from_json(scales, sequences),
+    }
+}
+
+/// The settings given on the command line replace the ones of the loaded project.
+fn apply_args(args: &Args, sequencer: &mut sequencer::Sequencer) {
+    if let Some(port) = &args.input_port {
+        sequencer.routing.input_port = port.clone();
+    }
+    if let Some(port) = &args.output_port {
+        sequencer.routing.output_port = port.clone();
+    }
+    if let Some(sync) = args.sync {
+        sequencer.tempo.sync = sync;
+    }
+    if let Some(bpm) = args.bpm {
+        sequencer.tempo.bpm = bpm;
+    }
+}
+
+/// Checks the files without starting the sequencer. Returns the exit code: 1 if the files
+/// can't be loaded.
+fn run_validate(args: &Args, scales: &DataSource, sequences: &DataSource) -> i32 {
+    match load(args, scales, sequences) {
+        Ok((_, warnings)) => {
+            for issue in &warnings.issues {
+                println!("{}", issue);
+            }
+            match &args.project_path {
+                Some(path) => println!("{} can be loaded", path),
+                None => println!("{} and {} can be loaded", scales.name(), sequences.name()),
+            }
+            0
+        }
+        Err(err) => {
+            eprintln!("{}", err);
+            1
+        }
     }
 }
 
 fn main() {
-    let sequencer = Arc::new(Mutex::new(get_sequencer_from_json(
-        SCALE_PATH,
-        SEQUENCE_PATH,
-    )));
-    _ = run_tui(sequencer.clone());
+    let args = match parse_args(std::env::args().skip(1)) {
+        Ok(args) => args,
+        Err(err) => {
+            eprintln!("{}\n\n{}", err, USAGE);
+            std::process::exit(2);
+        }
+    };
+    if args.help {
+        println!("{}", USAGE);
+        return;
+    }
+    let scales = DataSource::scales(args.scale_path.as_deref());
+    let sequences = DataSource::sequences(args.sequence_path.as_deref());
+    if args.validate {
+        std::process::exit(run_validate(&args, &scales, &sequences));
+    }
+
+    // Start anyway when the files can't be loaded, so that others can be loaded from the TUI
+    let (mut sequencer, error) = match load(&args, &scales, &sequences) {
+        Ok((sequencer, warnings)) if warnings.is_empty() => (sequencer, None),
+        Ok((sequencer, warnings)) => {
+            let warnings = format!("Loaded with warnings:\n{}", warnings);
+            (sequencer, Some(std::io::Error::other(warnings)))
+        }
+        Err(err) => (sequencer::Sequencer::placeholder(), Some(err.into())),
+    };
+    apply_args(&args, &mut sequencer);
+    let sequencer = Arc::new(Mutex::new(sequencer));
+    let paths = DataPaths {
+        scale_path: scales.save_path(),
+        sequence_path: sequences.save_path(),
+        project_path: args.project_path.clone(),
+    };
+    _ = run_tui(sequencer.clone(), paths, error);
     // run_debug(sequencer.clone()); // Use to debug Sequencer code: possibility to write logs in the console without the TUI
 }
@@ -1,62 +1,272 @@
-use crate::clock::Clock;
+use crate::arpeggiator::ArpMode;
+use crate::clock::{Clock, SyncMode};
 use crate::note::Note;
+use crate::output::{PitchRamp, Voice};
+use crate::scheduler::ScheduledEvent;
 use crate::sequencer::Sequencer;
 use midir::{Ignore, MidiInput, MidiInputConnection, MidiOutput, MidiOutputConnection};
 use std::io;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// Names of the MIDI ports the sequencer connects to. A port is used when its name contains
+/// the given name.
+pub(crate) struct MidiRouting {
+    pub(crate) input_port: String,
+    pub(crate) output_port: String,
+}
+
+impl MidiRouting {
+    pub(crate) fn new() -> Self {
+        Self {
+            input_port: "SequencerInput".to_string(),
+            output_port: "SequencerOutput".to_string(),
+        }
+    }
+}
 
 fn midi_input_handler(
     sequencer: Arc<Mutex<Sequencer>>,
     output_conn: Arc<Mutex<MidiOutputConnection>>,
 ) -> impl FnMut(u64, &[u8], &mut ()) + Send + 'static {
     let mut clock = Clock::new();
+    // Set when the upcoming note starts early and was already scheduled on the previous tick
+    let mut upcoming_scheduled = false;
+    // The upcoming note drawn on the previous tick, so that its humanization is only drawn once
+    let mut upcoming: Option<Note> = None;
 
     move |_stamp: u64, message: &[u8], _: &mut ()| match message[0] {
         0xF8 => {
+            let now = Instant::now();
+            clock.tick_received(now);
             let mut seq = sequencer.lock().unwrap();
             let current_note = seq.current_note();
+            let tick_length = current_note.duration.get_tick_length();
+            let mut conn = output_conn.lock().unwrap();
             if seq.started {
-                if clock.has_time_passed_note(current_note.duration.get_tick_length()) {
-                    let mut conn = output_conn.lock().unwrap();
-
-                    send_note(&mut conn, current_note, false);
+                if clock.has_time_passed_note(tick_length) {
                     seq.next_note();
-                    let current_note = seq.current_note();
-                    send_note(&mut conn, current_note, true);
-                    drop(conn);
-                    
+                    let upcoming_note = upcoming.take();
+                    // While the arpeggiator is playing, the sequence keeps its place silently
+                    if !seq.arpeggiator.is_playing() && !upcoming_scheduled {
+                        let current_note =
+                            upcoming_note.unwrap_or_else(|| seq.current_played_note());
+                        play_note_at_offset(&mut conn, &mut seq, current_note, now, clock.tick_period());
+                    }
+                    upcoming_scheduled = false;
+
                     if seq.current_note_index == 0 {
                         clock.reset_tick();
                     } else {
                         clock.note_played();
                     }
                 }
+                else if tick_length > 1
+                    && clock.has_time_passed_note(tick_length - 1)
+                    && !seq.arpeggiator.is_playing()
+                    // Otherwise it is played on its tick, once the reloaded data is in place
+                    && !seq.upcoming_note_may_change()
+                {
+                    // The next note starts on the next tick: if it is early, it must be scheduled now
+                    let upcoming_note = seq.upcoming_played_note();
+                    if upcoming_note.microtiming < 0f64 {
+                        let offset = 1f64 + upcoming_note.microtiming.max(-1f64);
+                        let tick_period = clock.tick_period();
+                        seq.scheduler.schedule(
+                            now + tick_period.mul_f64(offset),
+                            ScheduledEvent::NoteOn { note: upcoming_note, tick_period },
+                        );
+                        upcoming_scheduled = true;
+                    } else {
+                        upcoming = Some(upcoming_note);
+                    }
+                }
             }
             else {
-                let mut conn = output_conn.lock().unwrap();
-                send_note(&mut conn, current_note, true);
+                if !seq.arpeggiator.is_playing() {
+                    let current_note = seq.current_played_note();
+                    play_note_at_offset(&mut conn, &mut seq, current_note, now, clock.tick_period());
+                }
                 seq.started = true;
             }
+
+            let scale = seq.current_scale();
+            if let Some(arp_note) = seq.arpeggiator.tick(scale) {
+                play_note(&mut conn, &mut seq, arp_note, clock.tick_period(), now);
+            }
             clock.next();
         }
         0xFA | 0xFC => {
             clock.reset_tick();
+            upcoming_scheduled = false;
+            upcoming = None;
             let mut seq = sequencer.lock().unwrap();
             seq.reset();
         }
+        status if message.len() >= 3 && status & 0xF0 == 0x90 && message[2] > 0 => {
+            let mut seq = sequencer.lock().unwrap();
+            if seq.arpeggiator.mode != ArpMode::Off {
+                seq.arpeggiator.note_on(message[1], message[2]);
+            } else if seq.transpose.enabled {
+                let scale = seq.current_scale();
+                seq.transpose.note_on(message[1], &scale);
+            }
+        }
+        status if message.len() >= 3 && (status & 0xF0 == 0x80 || status & 0xF0 == 0x90) => {
+            let mut seq = sequencer.lock().unwrap();
+            seq.arpeggiator.note_off(message[1]);
+            if seq.arpeggiator.mode != ArpMode::Off && !seq.arpeggiator.is_playing() {
+                let mut conn = output_conn.lock().unwrap();
+                for voice in seq.voices.release_all() {
+                    stop_voice(&mut conn, voice);
+                }
+            }
+        }
         _ => {}
     }
 }
 
+/// Plays `note` on its clock tick, or schedules it a fraction of the tick period later when it
+/// has a positive microtiming offset.
+///
+/// Notes with a negative offset are scheduled on the previous tick by the clock handler, so
+/// here they can only start on time.
+fn play_note_at_offset(
+    conn: &mut MidiOutputConnection,
+    sequencer: &mut Sequencer,
+    note: Note,
+    tick_time: Instant,
+    tick_period: Duration,
+) {
+    if note.microtiming > 0f64 {
+        let at = tick_time + tick_period.mul_f64(note.microtiming.min(1f64));
+        sequencer.scheduler.schedule(at, ScheduledEvent::NoteOn { note, tick_period });
+    } else {
+        play_note(conn, sequencer, note, tick_period, tick_time);
+    }
+}
+
+/// Sends the scheduled events whose time has come.
+///
+/// The sequencer is locked before the output connection, as in the clock handler: taking them
+/// in the other order would deadlock with it. The connection is only locked when an event is
+/// due.
+pub(crate) fn play_scheduled_notes(
+    sequencer: Arc<Mutex<Sequencer>>,
+    output_conn: Arc<Mutex<MidiOutputConnection>>,
+) {
+    let mut sequencer = sequencer.lock().unwrap();
+    let now = Instant::now();
+    let events = sequencer.scheduler.take_due(now);
+    if events.is_empty() {
+        return;
+    }
+    let mut conn = output_conn.lock().unwrap();
+    for event in events {
+        match event {
+            ScheduledEvent::NoteOn { note, tick_period } => {
+                play_note(&mut conn, &mut sequencer, note, tick_period, now);
+            }
+            ScheduledEvent::NoteOff { voice_id } => {
+                if let Some(voice) = sequencer.voices.release(voice_id) {
+                    stop_voice(&mut conn, voice);
+                }
+            }
+        }
+    }
+}
+
+/// Bends the pitch of the voices that are gliding or modulated. Called by the main loop at
+/// the pitch update rate. Locks the sequencer first, like `play_scheduled_notes`.
+pub(crate) fn update_pitch_bends(
+    sequencer: Arc<Mutex<Sequencer>>,
+    output_conn: Arc<Mutex<MidiOutputConnection>>,
+) {
+    let mut sequencer = sequencer.lock().unwrap();
+    let mut conn = output_conn.lock().unwrap();
+    let now = Instant::now();
+    for voice in sequencer.voices.voices_mut() {
+        let bend = voice.bend_at(now, PITCH_BEND_RANGE);
+        if bend != voice.last_bend {
+            send_pitch_bend(&mut conn, bend, voice.channel);
+            voice.last_bend = bend;
+        }
+        if voice.ramp.as_ref().is_some_and(|ramp| ramp.is_finished(now)) {
+            voice.ramp = None;
+        }
+    }
+}
+
+/// Starts `note`, and schedules its note-off at the end of its gate.
+///
+/// A note with a glide slides from the pitch of the previous note: the voice still sounding
+/// is bent to the new pitch when the bend range allows it, otherwise the note is retriggered.
+/// The voices that the allocator takes back to make room for the note are stopped first.
+fn play_note(
+    conn: &mut MidiOutputConnection,
+    sequencer: &mut Sequencer,
+    note: Note,
+    tick_period: Duration,
+    at: Instant,
+) {
+    let gate = tick_period.mul_f64(note.get_gate_ticks());
+    let ramp = match (note.glide, sequencer.voices.last_pitch()) {
+        (Some(glide), Some(from)) => Some(PitchRamp {
+            from,
+            to: note.get_midi_number(),
+            start: at,
+            duration: tick_period.mul_f64(glide.time * 24f64),
+            curve: glide.curve,
+        }),
+        _ => None,
+    };
+
+    let legato_voice = ramp.clone().and_then(|ramp| {
+        sequencer
+            .voices
+            .glide_legato(note.clone(), ramp, PITCH_BEND_RANGE, at)
+    });
+    let voice = match legato_voice {
+        Some(voice) => {
+            send_parameter_locks(conn, &voice.note, voice.channel);
+            voice
+        }
+        None => {
+            let (voice, released) = sequencer.voices.allocate(
+                sequencer.output.mode,
+                note,
+                ramp,
+                PITCH_BEND_RANGE,
+                at,
+            );
+            for previous in released {
+                stop_voice(conn, previous);
+            }
+            let velocity = sequencer.output.output_velocity(voice.note.velocity);
+            start_voice(conn, &voice, velocity);
+            voice
+        }
+    };
+    sequencer
+        .scheduler
+        .schedule(at + gate, ScheduledEvent::NoteOff { voice_id: voice.id });
+}
+
+/// Connects to the input port, which sends the MIDI clock and the keyboard notes.
+///
+/// With the internal clock, the clock and transport messages of the port are ignored: the
+/// ticks come from `start_internal_clock` instead.
 pub(crate) fn create_input_connection(
     sequencer: Arc<Mutex<Sequencer>>,
     output_conn: Arc<Mutex<MidiOutputConnection>>,
+    input_port_name: &str,
+    sync: SyncMode,
 ) -> Result<MidiInputConnection<()>, io::Error> {
     let mut midi_in = MidiInput::new("Rust MIDI Input").unwrap();
     midi_in.ignore(Ignore::None);
 
     let in_ports = midi_in.ports();
-    let input_port_name = "SequencerInput";
     let port = match in_ports
         .iter()
         .find(|p| midi_in.port_name(p).unwrap().contains(input_port_name)) {
@@ -67,12 +277,48 @@ pub(crate) fn create_input_connection(
         ))}
     };
 
-    let handler = midi_input_handler(sequencer, output_conn);
+    let mut handler = midi_input_handler(sequencer, output_conn);
+    let follows_port_clock = sync == SyncMode::External;
     Ok(midi_in
-        .connect(port, "midir-read-input", handler, ())
+        .connect(
+            port,
+            "midir-read-input",
+            move |stamp: u64, message: &[u8], data: &mut ()| {
+                if follows_port_clock || !is_clock_message(message) {
+                    handler(stamp, message, data);
+                }
+            },
+            (),
+        )
         .unwrap())
 }
 
+fn is_clock_message(message: &[u8]) -> bool {
+    matches!(message.first(), Some(0xF8 | 0xFA | 0xFC))
+}
+
+/// Ticks the sequencer at the tempo of its settings until `on` is cleared, as the MIDI clock
+/// of a DAW would.
+pub(crate) fn start_internal_clock(
+    sequencer: Arc<Mutex<Sequencer>>,
+    output_conn: Arc<Mutex<MidiOutputConnection>>,
+    on: Arc<AtomicBool>,
+) {
+    let mut handler = midi_input_handler(sequencer.clone(), output_conn);
+    std::thread::spawn(move || {
+        handler(0, &[0xFA], &mut ());
+        let mut next_tick = Instant::now();
+        while on.load(Ordering::SeqCst) {
+            handler(0, &[0xF8], &mut ());
+            // Counted from the previous tick rather than from now, so that the tempo doesn't drift
+            next_tick += sequencer.lock().unwrap().tempo.tick_period();
+            if let Some(wait) = next_tick.checked_duration_since(Instant::now()) {
+                std::thread::sleep(wait);
+            }
+        }
+    });
+}
+
 fn send_pitch_bend(conn: &mut MidiOutputConnection, bend: i16, channel: u8) {
     let value = (bend + 8192).clamp(0, 16383) as u16;
     let lsb = (value & 0x7F) as u8; // lower 7 bits
@@ -83,37 +329,68 @@ fn send_pitch_bend(conn: &mut MidiOutputConnection, bend: i16, channel: u8) {
     conn.send(&message).unwrap();
 }
 
-fn pitch_bend_calculation(shift: f64, range: i16) -> i16 {
+/// Pitch bend range of the receiving instrument, in semitones.
+const PITCH_BEND_RANGE: i16 = 2;
+
+pub(crate) fn pitch_bend_calculation(shift: f64, range: i16) -> i16 {
     ((shift / range as f64) * (8192f64)) as i16
 }
 
-fn send_note(conn: &mut MidiOutputConnection, note: Note, on: bool) {
-    let channel = 0;
-    let status = if on { 0x90 | channel } else { 0x80 | channel };
-    let note_pitch = note.get_midi_number();
-    let note_number = note_pitch.floor() as u8;
-    let velocity = 100;
-
-    let shift = note_pitch - note_pitch.floor();
-    if on {
-        let bend = pitch_bend_calculation(shift, 2);
-        send_pitch_bend(conn, bend, channel);
+/// Sends the program change, panning, control changes and channel aftertouch stored on the
+/// note.
+fn send_parameter_locks(conn: &mut MidiOutputConnection, note: &Note, channel: u8) {
+    let channel = channel & 0x0F;
+    if let Some(program) = note.program {
+        conn.send(&[0xC0 | channel, program & 0x7F]).unwrap();
     }
+    if let Some(panning) = note.panning {
+        conn.send(&[0xB0 | channel, 10, panning & 0x7F]).unwrap();
+    }
+    for cc in &note.control_changes {
+        conn.send(&[0xB0 | channel, cc.controller & 0x7F, cc.value & 0x7F]).unwrap();
+    }
+    if let Some(pressure) = note.aftertouch {
+        conn.send(&[0xD0 | channel, pressure & 0x7F]).unwrap();
+    }
+}
+
+/// Sends the note-on of a voice, preceded by its parameter locks and pitch bend.
+fn start_voice(conn: &mut MidiOutputConnection, voice: &Voice, velocity: u8) {
+    let channel = voice.channel & 0x0F;
 
-    let msg = [status, note_number, velocity];
+    send_parameter_locks(conn, &voice.note, channel);
+    send_pitch_bend(conn, voice.last_bend, channel);
+
+    let msg = [0x90 | channel, voice.note_number, velocity];
     conn.send(&msg).unwrap();
 }
 
-pub fn stop_sequencer(conn: &mut MidiOutputConnection, sequencer: Arc<Mutex<Sequencer>>) {
-    send_note(conn, sequencer.lock().unwrap().current_note(), false);
-    sequencer.lock().unwrap().reset();
+fn stop_voice(conn: &mut MidiOutputConnection, voice: Voice) {
+    let channel = voice.channel & 0x0F;
+    let velocity = 100;
+
+    let msg = [0x80 | channel, voice.note_number, velocity];
+    conn.send(&msg).unwrap();
+}
+
+pub fn stop_sequencer(
+    sequencer: Arc<Mutex<Sequencer>>,
+    output_conn: Arc<Mutex<MidiOutputConnection>>,
+) {
+    let mut sequencer = sequencer.lock().unwrap();
+    let mut conn = output_conn.lock().unwrap();
+    for voice in sequencer.voices.release_all() {
+        stop_voice(&mut conn, voice);
+    }
+    sequencer.reset();
 }
 
-pub(crate) fn create_output_connection() -> Result<MidiOutputConnection, io::Error> {
+pub(crate) fn create_output_connection(
+    output_port_name: &str,
+) -> Result<MidiOutputConnection, io::Error> {
     let midi_out = MidiOutput::new("Rust Sequencer").unwrap();
 
     let out_ports = midi_out.ports();
-    let output_port_name = "SequencerOutput";
     let port = match out_ports
         .iter()
         .find(|p| midi_out.port_name(p).unwrap().contains(output_port_name)) {
@@ -0,0 +1,48 @@
+use std::f64::consts::PI;
+
+/// Periodic pitch modulation, in cents around the pitch of the note.
+#[derive(Clone, Copy, PartialEq)]
+pub(crate) struct Vibrato {
+    pub(crate) depth_cents: f64,
+    pub(crate) rate_hz: f64,
+    /// Time after the start of the note before the vibrato begins.
+    pub(crate) delay_ms: f64,
+}
+
+/// Pitch offset at the start of a note, going back to the note's pitch: a scoop when it starts
+/// below, a fall from above otherwise.
+#[derive(Clone, Copy, PartialEq)]
+pub(crate) struct PitchEnvelope {
+    pub(crate) start_cents: f64,
+    pub(crate) time_ms: f64,
+}
+
+/// Continuous pitch modulation of a sounding note, summed with its microtonal offset and
+/// sent as pitch bend.
+///
+/// `detune_cents` is a constant offset, set by the humanization of the sequence.
+#[derive(Clone, Copy, Default, PartialEq)]
+pub(crate) struct Modulation {
+    pub(crate) vibrato: Option<Vibrato>,
+    pub(crate) envelope: Option<PitchEnvelope>,
+    pub(crate) detune_cents: f64,
+}
+
+impl Modulation {
+    /// Pitch offset in semitones, `elapsed_ms` after the start of the note.
+    pub fn offset_at(&self, elapsed_ms: f64) -> f64 {
+        let mut cents = self.detune_cents;
+        if let Some(envelope) = self.envelope {
+            if elapsed_ms < envelope.time_ms {
+                cents += envelope.start_cents * (1f64 - elapsed_ms / envelope.time_ms);
+            }
+        }
+        if let Some(vibrato) = self.vibrato {
+            if elapsed_ms >= vibrato.delay_ms {
+                let seconds = (elapsed_ms - vibrato.delay_ms) / 1000f64;
+                cents += vibrato.depth_cents * (2f64 * PI * vibrato.rate_hz * seconds).sin();
+            }
+        }
+        cents / 100f64
+    }
+}
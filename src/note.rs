@@ -1,6 +1,8 @@
+use crate::modulation::Modulation;
 use std::fmt;
 use std::sync::{Arc};
 
+#[derive(Clone, PartialEq)]
 pub(crate) struct Scale {
     pub(crate) name: String,
     pub(crate) steps: Vec<f64>,
@@ -17,24 +19,139 @@ impl fmt::Display for Scale {
     }
 }
 
-#[derive(Clone)]
+impl Scale {
+    /// Finds the degree of the scale closest to a (possibly fractional) MIDI number.
+    ///
+    /// Returns the `(note_index, octave)` pair, looking into the neighbouring octaves so
+    /// that a pitch just below an octave boundary can snap to the next octave's root.
+    pub fn nearest_degree(&self, midi_number: f64) -> (usize, u8) {
+        let base_octave = (midi_number / 12f64).floor() as i32;
+        let mut nearest = (0, base_octave);
+        let mut smallest_distance = f64::MAX;
+
+        for octave in base_octave - 1..=base_octave + 1 {
+            for (i, step) in self.steps.iter().enumerate() {
+                let distance = (step + (octave * 12) as f64 - midi_number).abs();
+                if distance < smallest_distance {
+                    smallest_distance = distance;
+                    nearest = (i, octave);
+                }
+            }
+        }
+
+        (nearest.0, nearest.1.clamp(0, u8::MAX as i32) as u8)
+    }
+
+    /// The lowest and highest degrees, counted as by `Note::degree`, that can be played: their
+    /// MIDI numbers are within 0 to 127.
+    pub fn playable_degrees(&self) -> (i32, i32) {
+        let scale_len = self.steps.len() as i32;
+        let mut playable: Option<(i32, i32)> = None;
+        for (i, step) in self.steps.iter().enumerate() {
+            // The octaves where `step + octave * 12` is at least 0 and below 128
+            let lowest_octave = (-step / 12f64).ceil().max(0f64) as i32;
+            let highest_octave = (((128f64 - step) / 12f64).ceil() as i32 - 1).min(u8::MAX as i32);
+            if lowest_octave > highest_octave {
+                continue;
+            }
+            let (lowest, highest) =
+                (lowest_octave * scale_len + i as i32, highest_octave * scale_len + i as i32);
+            playable = Some(match playable {
+                None => (lowest, highest),
+                Some(range) => (range.0.min(lowest), range.1.max(highest)),
+            });
+        }
+        playable.unwrap_or((0, 0))
+    }
+}
+
+#[derive(Clone, PartialEq)]
 pub(crate) struct Note {
     pub(crate) scale: Arc<Scale>,
     pub(crate) octave: u8,
     pub(crate) duration: NoteDuration,
     pub(crate) note_index: usize,
     pub(crate) velocity: u8,
+    /// Sent as CC10 before the note-on.
+    pub(crate) panning: Option<u8>,
+    /// Signed offset from the clock tick the note falls on, as a fraction of a tick.
+    /// Negative values play the note early, positive values play it late.
+    pub(crate) microtiming: f64,
+    pub(crate) gate: Gate,
+    /// Parameter locks, sent on the note's channel right before its note-on.
+    pub(crate) control_changes: Vec<ControlChange>,
+    pub(crate) program: Option<u8>,
+    pub(crate) aftertouch: Option<u8>,
+    pub(crate) glide: Option<Glide>,
+    pub(crate) modulation: Modulation,
 }
 
 impl Note {
+    /// A note without any of the optional parameters: full gate, no locks and no modulation.
+    pub fn new(
+        scale: Arc<Scale>,
+        note_index: usize,
+        octave: u8,
+        duration: NoteDuration,
+        velocity: u8,
+    ) -> Note {
+        Note {
+            scale,
+            octave,
+            duration,
+            note_index,
+            velocity,
+            panning: None,
+            microtiming: 0f64,
+            gate: Gate::full(),
+            control_changes: vec![],
+            program: None,
+            aftertouch: None,
+            glide: None,
+            modulation: Modulation::default(),
+        }
+    }
+
     pub fn get_midi_number(&self) -> f64 {
-        let scale_note = match self.scale.steps.get(self.note_index as usize) {
+        let scale_note = match self.scale.steps.get(self.note_index) {
             None => self.scale.steps[self.scale.steps.len() - 1],
             Some(n) => *n,
         };
-        scale_note + (self.octave * 12) as f64
+        scale_note + self.octave as f64 * 12f64
     }
     
+    /// Position of the note in its scale, counted in degrees from the first degree of octave 0.
+    pub fn degree(&self) -> i32 {
+        self.octave as i32 * self.scale.steps.len() as i32 + self.note_index as i32
+    }
+
+    /// Moves the note to a position in its scale, as returned by `degree`. Positions out of
+    /// the MIDI range go to the nearest playable degree.
+    pub fn set_degree(&mut self, degree: i32) {
+        let scale_len = self.scale.steps.len() as i32;
+        let (lowest, highest) = self.scale.playable_degrees();
+        let degree = degree.clamp(lowest, highest);
+        self.note_index = (degree % scale_len) as usize;
+        self.octave = (degree / scale_len) as u8;
+    }
+
+    /// Returns a copy of the note moved by `degrees` steps of its scale.
+    ///
+    /// Moving past the last degree wraps into the next octave, so the note stays in the tuning.
+    pub fn transposed(&self, degrees: i32) -> Note {
+        let mut note = self.clone();
+        note.set_degree(self.degree() + degrees);
+        note
+    }
+
+    /// How long the note sounds, in clock ticks.
+    pub fn get_gate_ticks(&self) -> f64 {
+        match self.gate {
+            Gate::Percent(percent) => self.duration.duration * 24f64 * percent / 100f64,
+            Gate::Beats(beats) => beats * 24f64,
+        }
+    }
+
     pub fn get_common_name(&self) -> String {
         format!("{}{}", self.scale.note_names[self.note_index].clone(), self.octave)
     }
@@ -48,19 +165,159 @@ impl fmt::Display for Note {
             self.scale.name,
             self.octave,
             self.duration.duration,
-            self.scale.note_names[self.note_index as usize],
+            self.scale.note_names[self.note_index],
             self.velocity,
         )
     }
 }
 
-#[derive(Clone)]
+#[derive(Clone, PartialEq)]
 pub(crate) struct NoteDuration {
     pub(crate) duration: f64,
 }
 
+/// A MIDI control change (filter cutoff, mod wheel...) sent with a note.
+#[derive(Clone, Copy, PartialEq)]
+pub(crate) struct ControlChange {
+    pub(crate) controller: u8,
+    pub(crate) value: u8,
+}
+
+/// Pitch slide from the previous note to this one.
+///
+/// For a legato slide, the previous note must still be sounding when this one starts, so it
+/// needs a gate of 100% or more.
+#[derive(Clone, Copy, PartialEq)]
+pub(crate) struct Glide {
+    /// Length of the slide, in beats.
+    pub(crate) time: f64,
+    pub(crate) curve: GlideCurve,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub(crate) enum GlideCurve {
+    Linear,
+    /// Fast at first then settling on the pitch, like an analog portamento.
+    Exponential,
+    /// Slow at both ends.
+    SCurve,
+}
+
+impl GlideCurve {
+    pub fn next(self) -> Self {
+        match self {
+            GlideCurve::Linear => GlideCurve::Exponential,
+            GlideCurve::Exponential => GlideCurve::SCurve,
+            GlideCurve::SCurve => GlideCurve::Linear,
+        }
+    }
+
+    /// Maps the progress of the glide, from 0 to 1, to the progress of the pitch.
+    pub fn apply(&self, t: f64) -> f64 {
+        let t = t.clamp(0f64, 1f64);
+        match self {
+            GlideCurve::Linear => t,
+            GlideCurve::Exponential => (1f64 - (-5f64 * t).exp()) / (1f64 - (-5f64).exp()),
+            GlideCurve::SCurve => t * t * (3f64 - 2f64 * t),
+        }
+    }
+}
+
+impl fmt::Display for GlideCurve {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            GlideCurve::Linear => "linear",
+            GlideCurve::Exponential => "exponential",
+            GlideCurve::SCurve => "S-curve",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// How long a note sounds, independently of when the next note starts.
+#[derive(Clone, Copy, PartialEq)]
+pub(crate) enum Gate {
+    /// Percentage of the note's duration: under 100 is staccato, over 100 overlaps the next note.
+    Percent(f64),
+    /// Absolute length, in beats.
+    Beats(f64),
+}
+
+impl Gate {
+    pub fn full() -> Self {
+        Gate::Percent(100f64)
+    }
+}
+
 impl NoteDuration {
     pub fn get_tick_length(&self) -> u8 {
         (self.duration * 24f64).round() as u8
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn twelve_tet() -> Arc<Scale> {
+        Arc::new(Scale {
+            name: "12-TET".to_string(),
+            steps: (0..12).map(|step| step as f64).collect(),
+            note_names: (0..12).map(|step| step.to_string()).collect(),
+        })
+    }
+
+    fn note(note_index: usize, octave: u8) -> Note {
+        Note::new(twelve_tet(), note_index, octave, NoteDuration { duration: 1f64 }, 100)
+    }
+
+    #[test]
+    fn playable_degrees_stay_in_the_midi_range() {
+        assert_eq!(twelve_tet().playable_degrees(), (0, 127));
+        let high = Scale {
+            name: "High".to_string(),
+            steps: vec![6f64, 18f64],
+            note_names: vec!["a".to_string(), "b".to_string()],
+        };
+        // 6 + 10 * 12 = 126 and 18 + 9 * 12 = 126 are the highest steps below 128
+        assert_eq!(high.playable_degrees(), (0, 20));
+        let below_zero = Scale {
+            name: "Below zero".to_string(),
+            steps: vec![-13f64, 0f64, 8f64],
+            note_names: vec!["a".to_string(), "b".to_string(), "c".to_string()],
+        };
+        // 0 in octave 0 is the lowest, -13 + 11 * 12 = 119 the highest
+        assert_eq!(below_zero.playable_degrees(), (1, 33));
+    }
+
+    #[test]
+    fn set_degree_clamps_to_the_midi_range() {
+        let mut note = note(0, 5);
+        note.set_degree(10_000);
+        assert_eq!(note.get_midi_number(), 127f64);
+        note.set_degree(-5);
+        assert_eq!(note.get_midi_number(), 0f64);
+    }
+
+    #[test]
+    fn transposed_stops_at_the_top_of_the_midi_range() {
+        let mut note = note(4, 10);
+        for _ in 0..30 {
+            note = note.transposed(12);
+        }
+        assert_eq!((note.note_index, note.octave), (7, 10));
+        assert_eq!(note.transposed(-1).get_midi_number(), 126f64);
+    }
+
+    #[test]
+    fn midi_number_of_high_octaves_does_not_overflow() {
+        assert_eq!(note(0, 255).get_midi_number(), 3060f64);
+    }
+
+    #[test]
+    fn nearest_degree_snaps_to_the_next_octave() {
+        let scale = twelve_tet();
+        assert_eq!(scale.nearest_degree(60.2), (0, 5));
+        assert_eq!(scale.nearest_degree(71.7), (0, 6));
+    }
+}
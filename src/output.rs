@@ -0,0 +1,319 @@
+use crate::midi::pitch_bend_calculation;
+use crate::note::{GlideCurve, Note};
+use std::fmt;
+use std::time::{Duration, Instant};
+
+/// How the notes are spread over the MIDI channels of the output.
+///
+/// The microtonal offset of a note is sent as pitch bend, which applies to a whole channel.
+/// `Mono` plays every note on the first channel, so a note always stops when the next one
+/// starts. `Poly` gives each sounding note its own channel, so notes can overlap while each
+/// keeps its own pitch bend.
+#[derive(Clone, Copy, PartialEq)]
+pub(crate) enum OutputMode {
+    Mono,
+    Poly { channels: u8 },
+}
+
+impl OutputMode {
+    pub(crate) fn next(self) -> Self {
+        match self {
+            OutputMode::Mono => OutputMode::Poly { channels: 2 },
+            OutputMode::Poly { channels } if channels < 16 => OutputMode::Poly {
+                channels: channels + 1,
+            },
+            OutputMode::Poly { .. } => OutputMode::Mono,
+        }
+    }
+
+    pub(crate) fn previous(self) -> Self {
+        match self {
+            OutputMode::Mono => OutputMode::Poly { channels: 16 },
+            OutputMode::Poly { channels } if channels > 2 => OutputMode::Poly {
+                channels: channels - 1,
+            },
+            OutputMode::Poly { .. } => OutputMode::Mono,
+        }
+    }
+}
+
+impl fmt::Display for OutputMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            OutputMode::Mono => write!(f, "Mono"),
+            OutputMode::Poly { channels } => write!(f, "Poly ({} channels)", channels),
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub(crate) enum VelocityCurve {
+    Linear,
+    /// Softens the low velocities, leaving more room for accents.
+    Exponential,
+    /// Ignores the velocity of the notes: every note plays at the velocity scale.
+    Fixed,
+}
+
+impl VelocityCurve {
+    pub(crate) fn next(self) -> Self {
+        match self {
+            VelocityCurve::Linear => VelocityCurve::Exponential,
+            VelocityCurve::Exponential => VelocityCurve::Fixed,
+            VelocityCurve::Fixed => VelocityCurve::Linear,
+        }
+    }
+
+    pub(crate) fn previous(self) -> Self {
+        match self {
+            VelocityCurve::Linear => VelocityCurve::Fixed,
+            VelocityCurve::Exponential => VelocityCurve::Linear,
+            VelocityCurve::Fixed => VelocityCurve::Exponential,
+        }
+    }
+}
+
+impl fmt::Display for VelocityCurve {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            VelocityCurve::Linear => "Linear",
+            VelocityCurve::Exponential => "Exponential",
+            VelocityCurve::Fixed => "Fixed",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// Settings of the MIDI output.
+///
+/// `velocity_scale` is a percentage applied to every velocity after `velocity_curve`.
+pub(crate) struct OutputSettings {
+    pub(crate) mode: OutputMode,
+    pub(crate) velocity_scale: u8,
+    pub(crate) velocity_curve: VelocityCurve,
+}
+
+impl OutputSettings {
+    pub(crate) fn new() -> Self {
+        Self {
+            mode: OutputMode::Mono,
+            velocity_scale: 100,
+            velocity_curve: VelocityCurve::Linear,
+        }
+    }
+
+    /// The velocity sent for a note of velocity `velocity`.
+    pub(crate) fn output_velocity(&self, velocity: u8) -> u8 {
+        let level = velocity.min(127) as f64 / 127f64;
+        let curved = match self.velocity_curve {
+            VelocityCurve::Linear => level,
+            VelocityCurve::Exponential => level * level,
+            VelocityCurve::Fixed => 1f64,
+        };
+        (curved * 127f64 * self.velocity_scale as f64 / 100f64)
+            .round()
+            .clamp(1f64, 127f64) as u8
+    }
+}
+
+/// A slide of the pitch of a voice, rendered as pitch bend by the main loop.
+#[derive(Clone)]
+pub(crate) struct PitchRamp {
+    pub(crate) from: f64,
+    pub(crate) to: f64,
+    pub(crate) start: Instant,
+    pub(crate) duration: Duration,
+    pub(crate) curve: GlideCurve,
+}
+
+impl PitchRamp {
+    pub(crate) fn pitch_at(&self, now: Instant) -> f64 {
+        let elapsed = now.saturating_duration_since(self.start).as_secs_f64();
+        let t = match self.duration.as_secs_f64() {
+            0f64 => 1f64,
+            duration => elapsed / duration,
+        };
+        self.from + (self.to - self.from) * self.curve.apply(t)
+    }
+
+    pub(crate) fn is_finished(&self, now: Instant) -> bool {
+        now >= self.start + self.duration
+    }
+
+    /// Whether the whole ramp can be played by bending `note_number`.
+    fn fits(&self, note_number: u8, bend_range: i16) -> bool {
+        (self.from - note_number as f64).abs() <= bend_range as f64
+            && (self.to - note_number as f64).abs() <= bend_range as f64
+    }
+}
+
+/// A note that was sent as a note-on and still needs its note-off.
+///
+/// `note_number` is the MIDI note that was sent, which stays the same while the voice glides
+/// to the pitch of the following notes. `started_at` is when the current note started, which
+/// its modulation is timed from. `last_bend` is the last pitch bend sent on its channel.
+#[derive(Clone)]
+pub(crate) struct Voice {
+    pub(crate) id: u64,
+    pub(crate) channel: u8,
+    pub(crate) note_number: u8,
+    pub(crate) note: Note,
+    pub(crate) ramp: Option<PitchRamp>,
+    pub(crate) started_at: Instant,
+    pub(crate) last_bend: i16,
+}
+
+impl Voice {
+    pub(crate) fn pitch_at(&self, now: Instant) -> f64 {
+        let pitch = match &self.ramp {
+            Some(ramp) => ramp.pitch_at(now),
+            None => self.note.get_midi_number(),
+        };
+        let elapsed_ms = now.saturating_duration_since(self.started_at).as_secs_f64() * 1000f64;
+        pitch + self.note.modulation.offset_at(elapsed_ms)
+    }
+
+    pub(crate) fn bend_at(&self, now: Instant, bend_range: i16) -> i16 {
+        pitch_bend_calculation(self.pitch_at(now) - self.note_number as f64, bend_range)
+    }
+}
+
+/// Keeps track of the sounding voices and decides which channel a new note goes to.
+///
+/// `last_pitch` is the pitch of the last note that started, which glides start from.
+pub(crate) struct VoiceAllocator {
+    voices: Vec<Voice>,
+    next_id: u64,
+    next_channel: u8,
+    last_pitch: Option<f64>,
+}
+
+impl VoiceAllocator {
+    pub(crate) fn new() -> Self {
+        Self {
+            voices: vec![],
+            next_id: 0,
+            next_channel: 0,
+            last_pitch: None,
+        }
+    }
+
+    pub(crate) fn last_pitch(&self) -> Option<f64> {
+        self.last_pitch
+    }
+
+    pub(crate) fn voices_mut(&mut self) -> impl Iterator<Item = &mut Voice> {
+        self.voices.iter_mut()
+    }
+
+    /// Allocates a voice for `note`. The voice follows `ramp` if it fits in the bend range
+    /// around the note's MIDI number.
+    ///
+    /// Returns the new voice and the voices that must be stopped to make room for it: every
+    /// voice in `Mono` mode, or the oldest one when all the channels are busy in `Poly` mode.
+    pub(crate) fn allocate(
+        &mut self,
+        mode: OutputMode,
+        note: Note,
+        ramp: Option<PitchRamp>,
+        bend_range: i16,
+        at: Instant,
+    ) -> (Voice, Vec<Voice>) {
+        let (channel, released) = match mode {
+            OutputMode::Mono => (0, self.release_all()),
+            OutputMode::Poly { channels } => {
+                let free_channel = (0..channels)
+                    .map(|i| (self.next_channel + i) % channels)
+                    .find(|c| !self.voices.iter().any(|v| v.channel == *c));
+                match free_channel {
+                    Some(c) => (c, vec![]),
+                    None => {
+                        let oldest = self.voices.remove(0);
+                        (oldest.channel, vec![oldest])
+                    }
+                }
+            }
+        };
+        if let OutputMode::Poly { channels } = mode {
+            self.next_channel = (channel + 1) % channels;
+        }
+
+        let note_number = note.get_midi_number().floor().clamp(0f64, 127f64) as u8;
+        self.last_pitch = Some(note.get_midi_number());
+        let mut voice = Voice {
+            id: self.next_id,
+            channel,
+            note_number,
+            note,
+            ramp: ramp.filter(|r| r.fits(note_number, bend_range)),
+            started_at: at,
+            last_bend: 0,
+        };
+        voice.last_bend = voice.bend_at(at, bend_range);
+        self.next_id += 1;
+        self.voices.push(voice.clone());
+        (voice, released)
+    }
+
+    /// Makes the last sounding voice slide to `note` without a new note-on, when `ramp` fits
+    /// in the bend range around its MIDI number.
+    ///
+    /// The voice gets a new id, so that the note-off scheduled for its previous note is ignored.
+    pub(crate) fn glide_legato(
+        &mut self,
+        note: Note,
+        ramp: PitchRamp,
+        bend_range: i16,
+        at: Instant,
+    ) -> Option<Voice> {
+        let voice = self.voices.last_mut()?;
+        if !ramp.fits(voice.note_number, bend_range) {
+            return None;
+        }
+        self.last_pitch = Some(note.get_midi_number());
+        voice.id = self.next_id;
+        voice.note = note;
+        voice.ramp = Some(ramp);
+        voice.started_at = at;
+        self.next_id += 1;
+        Some(voice.clone())
+    }
+
+    /// Removes the voice from the sounding ones. Returns `None` if it was already stopped.
+    pub(crate) fn release(&mut self, id: u64) -> Option<Voice> {
+        let i = self.voices.iter().position(|v| v.id == id)?;
+        Some(self.voices.remove(i))
+    }
+
+    pub(crate) fn release_all(&mut self) -> Vec<Voice> {
+        std::mem::take(&mut self.voices)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn velocities(curve: VelocityCurve, velocity_scale: u8) -> Vec<u8> {
+        let settings = OutputSettings {
+            velocity_curve: curve,
+            velocity_scale,
+            ..OutputSettings::new()
+        };
+        [0, 64, 127].iter().map(|velocity| settings.output_velocity(*velocity)).collect()
+    }
+
+    #[test]
+    fn velocity_curves_map_the_note_velocities() {
+        // A velocity of 0 would be read as a note off, so the lowest sent is 1
+        assert_eq!(velocities(VelocityCurve::Linear, 100), vec![1, 64, 127]);
+        assert_eq!(velocities(VelocityCurve::Exponential, 100), vec![1, 32, 127]);
+        assert_eq!(velocities(VelocityCurve::Fixed, 100), vec![127, 127, 127]);
+    }
+
+    #[test]
+    fn velocity_scale_applies_after_the_curve() {
+        assert_eq!(velocities(VelocityCurve::Linear, 50), vec![1, 32, 64]);
+        assert_eq!(velocities(VelocityCurve::Fixed, 50), vec![64, 64, 64]);
+    }
+}
@@ -0,0 +1,153 @@
+use crate::json::LoadError;
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Name of the folder of the sequencer in the config and data directories.
+const APP_DIR: &str = "microtonal-sequencer";
+
+const SCALE_FILE: &str = "scales.json";
+const SEQUENCE_FILE: &str = "sequences.json";
+
+/// The scale and sequence files of the repository, built into the program to start with when
+/// none is found.
+const DEFAULT_SCALES: &str = include_str!("../data/scales.json");
+const DEFAULT_SEQUENCES: &str = include_str!("../data/sequences.json");
+
+/// A scale or sequence file to load: one found on disk, or the copy built into the program.
+pub(crate) enum DataSource {
+    File(PathBuf),
+    Bundled { file_name: &'static str, text: &'static str },
+}
+
+impl DataSource {
+    pub(crate) fn scales(path: Option<&str>) -> Self {
+        Self::find(path, SCALE_FILE, DEFAULT_SCALES)
+    }
+
+    pub(crate) fn sequences(path: Option<&str>) -> Self {
+        Self::find(path, SEQUENCE_FILE, DEFAULT_SEQUENCES)
+    }
+
+    /// The scale file saved at `path`, or the built-in one while it hasn't been saved.
+    pub(crate) fn saved_scales(path: &str) -> Self {
+        Self::saved(path, SCALE_FILE, DEFAULT_SCALES)
+    }
+
+    pub(crate) fn saved_sequences(path: &str) -> Self {
+        Self::saved(path, SEQUENCE_FILE, DEFAULT_SEQUENCES)
+    }
+
+    fn saved(path: &str, file_name: &'static str, text: &'static str) -> Self {
+        match Path::new(path).exists() {
+            true => DataSource::File(PathBuf::from(path)),
+            false => DataSource::Bundled { file_name, text },
+        }
+    }
+
+    /// The file given on the command line, or the first one found with `file_name`.
+    fn find(path: Option<&str>, file_name: &'static str, text: &'static str) -> Self {
+        if let Some(path) = path {
+            return DataSource::File(PathBuf::from(path));
+        }
+        match search_dirs().into_iter().map(|dir| dir.join(file_name)).find(|p| p.is_file()) {
+            Some(path) => DataSource::File(path),
+            None => DataSource::Bundled { file_name, text },
+        }
+    }
+
+    /// Names the file in errors.
+    pub(crate) fn name(&self) -> String {
+        match self {
+            DataSource::File(path) => path.to_string_lossy().to_string(),
+            DataSource::Bundled { file_name, .. } => format!("built-in {}", file_name),
+        }
+    }
+
+    pub(crate) fn read(&self) -> Result<String, LoadError> {
+        match self {
+            DataSource::File(path) => fs::read_to_string(path).map_err(|source| LoadError::Io {
+                path: self.name(),
+                source,
+            }),
+            DataSource::Bundled { text, .. } => Ok(text.to_string()),
+        }
+    }
+
+    /// Where the file is saved: built-in files are saved to the data directory.
+    pub(crate) fn save_path(&self) -> String {
+        let path = match self {
+            DataSource::File(path) => path.clone(),
+            DataSource::Bundled { file_name, .. } => data_dir().join(file_name),
+        };
+        path.to_string_lossy().to_string()
+    }
+}
+
+/// `$XDG_DATA_HOME/microtonal-sequencer`, where files are saved when none was loaded.
+pub(crate) fn data_dir() -> PathBuf {
+    xdg_dir("XDG_DATA_HOME", &[".local", "share"], "LOCALAPPDATA").join(APP_DIR)
+}
+
+/// `$XDG_CONFIG_HOME/microtonal-sequencer`.
+fn config_dir() -> PathBuf {
+    xdg_dir("XDG_CONFIG_HOME", &[".config"], "APPDATA").join(APP_DIR)
+}
+
+/// Value of `XDG_DATA_DIRS` to use when it is unset or empty, as the specification says.
+const DEFAULT_DATA_DIRS: &str = "/usr/local/share:/usr/share";
+
+/// The folders searched for data files, in order: the data and config directories, the data
+/// directories of `XDG_DATA_DIRS`, then the `data` folder of the working directory.
+fn search_dirs() -> Vec<PathBuf> {
+    let mut dirs = vec![data_dir(), config_dir()];
+    dirs.extend(system_data_dirs(&env::var("XDG_DATA_DIRS").unwrap_or_default()));
+    dirs.push(PathBuf::from("data"));
+    dirs
+}
+
+/// The folders of the sequencer in the data directories listed in `data_dirs`.
+fn system_data_dirs(data_dirs: &str) -> Vec<PathBuf> {
+    let data_dirs = match data_dirs {
+        "" => DEFAULT_DATA_DIRS,
+        data_dirs => data_dirs,
+    };
+    env::split_paths(data_dirs)
+        .filter(|dir| dir.is_absolute())
+        .map(|dir| dir.join(APP_DIR))
+        .collect()
+}
+
+/// The directory named by the variable `var`, or the default of the XDG specification under
+/// the home directory. Windows has no home variable: `windows_var` is used instead.
+fn xdg_dir(var: &str, home_default: &[&str], windows_var: &str) -> PathBuf {
+    // The specification says relative paths are invalid and must be ignored
+    if let Some(dir) = env::var_os(var).map(PathBuf::from).filter(|dir| dir.is_absolute()) {
+        return dir;
+    }
+    match env::var_os("HOME") {
+        Some(home) => home_default.iter().fold(PathBuf::from(home), |dir, part| dir.join(part)),
+        None => env::var_os(windows_var).map(PathBuf::from).unwrap_or_default(),
+    }
+}
+
+/// The files the TUI saves to.
+pub(crate) struct DataPaths {
+    pub(crate) scale_path: String,
+    pub(crate) sequence_path: String,
+    /// Set when a project was loaded: everything is then saved to it.
+    pub(crate) project_path: Option<String>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(unix)]
+    #[test]
+    fn data_dirs_default_to_the_system_share_folders() {
+        let share = |dir: &str| Path::new(dir).join(APP_DIR);
+        assert_eq!(system_data_dirs(""), vec![share("/usr/local/share"), share("/usr/share")]);
+        assert_eq!(system_data_dirs("/opt/share:relative"), vec![share("/opt/share")]);
+    }
+}
@@ -0,0 +1,50 @@
+use crate::note::Note;
+use std::time::{Duration, Instant};
+
+pub(crate) enum ScheduledEvent {
+    /// Starts a note. Its gate and glide are measured with the clock's `tick_period`.
+    NoteOn { note: Note, tick_period: Duration },
+    /// Stops the voice with this id, if it is still sounding.
+    NoteOff { voice_id: u64 },
+}
+
+/// Events waiting to be sent at a precise instant, between two clock ticks.
+///
+/// The MIDI clock only gives 24 ticks per beat, so notes with a microtiming offset and the
+/// note-offs ending the gate of each note are placed here by the clock handler, and sent by
+/// the main loop when their instant is reached.
+pub(crate) struct Scheduler {
+    pending: Vec<(Instant, ScheduledEvent)>,
+}
+
+impl Scheduler {
+    pub(crate) fn new() -> Self {
+        Self { pending: vec![] }
+    }
+
+    pub(crate) fn schedule(&mut self, at: Instant, event: ScheduledEvent) {
+        self.pending.push((at, event));
+    }
+
+    /// Removes and returns the events due at `now`, in the order they should be sent.
+    pub(crate) fn take_due(&mut self, now: Instant) -> Vec<ScheduledEvent> {
+        let mut due: Vec<(Instant, ScheduledEvent)> = vec![];
+        let mut i = 0;
+        while i < self.pending.len() {
+            if self.pending[i].0 <= now {
+                due.push(self.pending.remove(i));
+            } else {
+                i += 1;
+            }
+        }
+        due.sort_by_key(|(at, _)| *at);
+        due.into_iter().map(|(_, event)| event).collect()
+    }
+
+    /// Drops the notes that have not started yet. Pending note-offs are kept, so that the
+    /// notes already sounding still stop.
+    pub(crate) fn cancel_note_ons(&mut self) {
+        self.pending
+            .retain(|(_, event)| matches!(event, ScheduledEvent::NoteOff { .. }));
+    }
+}
@@ -1,6 +1,14 @@
 use std::fmt;
 use std::sync::{Arc};
-use crate::note::{Note, Scale};
+use crate::arpeggiator::Arpeggiator;
+use crate::clock::{Tempo, MAX_NOTE_BEATS};
+use crate::generator::{Generator, MelodyModel};
+use crate::humanize::Humanize;
+use crate::midi::MidiRouting;
+use crate::note::{Gate, Note, NoteDuration, Scale};
+use crate::output::{OutputSettings, VoiceAllocator};
+use crate::random::Rng;
+use crate::scheduler::Scheduler;
 
 /// A sequence of notes that plays using a specified scale.
 ///
@@ -12,12 +20,17 @@ use crate::note::{Note, Scale};
 /// - `repeat` controls how many times the sequence will repeat:
 ///     - `0` means it plays once.
 ///     - `n` means it plays `n + 1` times.
+/// - `humanize` adds random deviations to the notes when they are played, leaving `notes`
+///   unchanged.
+/// - `generator`, when set, writes a new melody over `notes` every few loops.
 #[derive(Clone)]
 pub(crate) struct Sequence {
     pub(crate) name: String,
     scale: Arc<Scale>,
     pub notes: Vec<Note>,
     pub(crate) repeat: usize,
+    pub(crate) humanize: Humanize,
+    pub(crate) generator: Option<Generator>,
 }
 
 impl Sequence {
@@ -27,6 +40,154 @@ impl Sequence {
             scale,
             notes,
             repeat: 0,
+            humanize: Humanize::default(),
+            generator: None,
+        }
+    }
+
+    pub(crate) fn scale(&self) -> Arc<Scale> {
+        self.scale.clone()
+    }
+
+    /// Writes a new melody over the notes, if the sequence has a generator.
+    pub(crate) fn generate_melody(&mut self) {
+        if let Some(generator) = &mut self.generator {
+            generator.generate(&mut self.notes);
+        }
+    }
+
+    /// Moves every note by `degrees` steps of the scale.
+    pub(crate) fn transpose(&mut self, degrees: i32) {
+        for note in &mut self.notes {
+            note.set_degree(note.degree() + degrees);
+        }
+    }
+
+    /// Mirrors the melody around the `pivot` degree: a note a step above it goes a step below.
+    pub(crate) fn invert(&mut self, pivot: i32) {
+        for note in &mut self.notes {
+            note.set_degree(2 * pivot - note.degree());
+        }
+    }
+
+    /// Plays the notes backwards.
+    pub(crate) fn retrograde(&mut self) {
+        self.notes.reverse();
+    }
+
+    /// Moves every note `steps` places later, the last notes coming back at the start.
+    pub(crate) fn rotate(&mut self, steps: i32) {
+        if self.notes.is_empty() {
+            return;
+        }
+        let steps = steps.rem_euclid(self.notes.len() as i32) as usize;
+        self.notes.rotate_right(steps);
+    }
+
+    /// Multiplies the durations of the notes, and their gates when given in beats. Durations
+    /// stay between one clock tick and `MAX_NOTE_BEATS`.
+    pub(crate) fn scale_durations(&mut self, factor: f64) {
+        for note in &mut self.notes {
+            note.duration.duration =
+                (note.duration.duration * factor).clamp(1f64 / 24f64, MAX_NOTE_BEATS);
+            if let Gate::Beats(beats) = note.gate {
+                note.gate = Gate::Beats(beats * factor);
+            }
+        }
+    }
+
+    /// Moves the notes by octaves until they fit between the `lowest` and `highest` degrees.
+    /// If the range is smaller than an octave, the notes that still do not fit are clamped.
+    pub(crate) fn fold_octaves(&mut self, lowest: i32, highest: i32) {
+        let scale_len = self.scale.steps.len() as i32;
+        for note in &mut self.notes {
+            let mut degree = note.degree();
+            while degree > highest && degree - scale_len >= 0 {
+                degree -= scale_len;
+            }
+            while degree < lowest {
+                degree += scale_len;
+            }
+            note.set_degree(degree.min(highest).max(lowest));
+        }
+    }
+
+    /// Moves the sequence into `scale`. Returns, for each note, how far its new pitch is from
+    /// the old one, in cents.
+    pub(crate) fn retune(&mut self, scale: Arc<Scale>, mode: RetuneMode) -> Vec<f64> {
+        let old_len = self.scale.steps.len();
+        let new_len = scale.steps.len();
+        let errors = self
+            .notes
+            .iter_mut()
+            .map(|note| retune_note(note, &scale, mode))
+            .collect();
+        self.scale = scale;
+
+        if let Some(generator) = &mut self.generator {
+            generator.rescale(old_len, new_len);
+        }
+        // A Markov model only knows the degrees of the old scale: learn again from the new notes
+        let notes = self.notes.clone();
+        self.train_generator(&[&notes]);
+        errors
+    }
+
+    /// Trains the Markov model of the generator, if it uses one, on `melodies`.
+    pub(crate) fn train_generator(&mut self, melodies: &[&[Note]]) {
+        if let Some(generator) = &mut self.generator {
+            if let MelodyModel::Markov { .. } = generator.model {
+                generator.model = MelodyModel::markov(&self.scale, melodies);
+            }
+        }
+    }
+}
+
+/// Moves `note` into `scale`, and returns how far its new pitch is from the old one, in cents.
+pub(crate) fn retune_note(note: &mut Note, scale: &Arc<Scale>, mode: RetuneMode) -> f64 {
+    let old_len = note.scale.steps.len();
+    let new_len = scale.steps.len();
+    let old_pitch = note.get_midi_number();
+    let (note_index, octave) = match mode {
+        RetuneMode::NearestPitch => scale.nearest_degree(old_pitch),
+        RetuneMode::Degree if old_len == new_len => (note.note_index, note.octave),
+        RetuneMode::Degree => {
+            let position = note.note_index as f64 * new_len as f64 / old_len as f64;
+            ((position.round() as usize).min(new_len - 1), note.octave)
+        }
+    };
+    note.scale = scale.clone();
+    note.note_index = note_index;
+    note.octave = octave;
+    // A degree kept in a scale with higher steps can go past the MIDI range
+    note.set_degree(note.degree());
+    (note.get_midi_number() - old_pitch) * 100f64
+}
+
+/// How the notes of a sequence are moved into another scale.
+#[derive(Clone, Copy, PartialEq)]
+pub(crate) enum RetuneMode {
+    /// Each note goes to the degree of the new scale closest to its pitch.
+    NearestPitch,
+    /// Each note keeps its octave and its degree number. When the scales have different
+    /// sizes, the degree keeps its relative place in the octave instead.
+    Degree,
+}
+
+impl RetuneMode {
+    pub(crate) fn next(self) -> Self {
+        match self {
+            RetuneMode::NearestPitch => RetuneMode::Degree,
+            RetuneMode::Degree => RetuneMode::NearestPitch,
+        }
+    }
+}
+
+impl fmt::Display for RetuneMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RetuneMode::NearestPitch => write!(f, "Nearest pitch"),
+            RetuneMode::Degree => write!(f, "Degree"),
         }
     }
 }
@@ -51,65 +212,390 @@ impl fmt::Display for Sequence {
 }
 
 
+/// Plays a list of `Sequence`s one after the other.
+///
+/// - `voices` tracks the notes sounding on the output, whether they come from the sequence or
+///   from the arpeggiator, and `output` decides how they are sent.
+/// - `arpeggiator` takes over the output while notes are held on the MIDI input.
+/// - `transpose` shifts the playing notes by the scale degrees set from the MIDI input.
+/// - `scheduler` holds the notes that start between two clock ticks.
+/// - `scales` are all the loaded scales, which the sequences can be retuned to.
+/// - `queued_sequence` is the sequence to jump to at the end of the current loop.
+/// - `humanizer` draws the deviations of the humanized notes. It is seeded again when a new
+///   sequence starts playing, `humanized_sequence` being the sequence it was seeded for.
+/// - `pitch_update_rate` is how many times per second the pitch bend of gliding and modulated
+///   voices is updated.
+/// - `routing` names the MIDI ports to connect to, and `tempo` where the clock comes from.
 pub(crate) struct Sequencer {
     pub(crate) sequences: Vec<Sequence>,
+    pub(crate) scales: Vec<Arc<Scale>>,
     pub(crate) current_sequence_index: usize,
+    pub(crate) queued_sequence: Option<usize>,
     times_repeated: usize,
     pub(crate) current_note_index: usize,
     pub(crate) started: bool,
+    pub(crate) voices: VoiceAllocator,
+    pub(crate) output: OutputSettings,
+    pub(crate) arpeggiator: Arpeggiator,
+    pub(crate) transpose: KeyboardTranspose,
+    pub(crate) scheduler: Scheduler,
+    pub(crate) pitch_update_rate: u32,
+    humanizer: Rng,
+    humanized_sequence: Option<usize>,
+    pub(crate) routing: MidiRouting,
+    pub(crate) tempo: Tempo,
+    /// Data re-read from edited files, put in place at the end of the playing loop.
+    pending_reload: Option<Reload>,
+    /// Number of reloads applied, which make the edit history obsolete.
+    reload_count: u64,
+}
+
+/// Scales and sequences re-read from their files after they were edited in another program.
+pub(crate) enum Reload {
+    /// The scales and sequences of the two-file layout.
+    Files {
+        scales: Vec<Arc<Scale>>,
+        sequences: Vec<Sequence>,
+    },
+    /// A project, settings included.
+    Project(Box<Sequencer>),
+}
+
+/// Transposes the sequence from the notes received on the MIDI input, like the keyboard
+/// transpose of hardware sequencers.
+///
+/// The interval between `root` and the last received note is counted in degrees of the playing
+/// scale, so the transposition stays in the tuning instead of moving by semitones.
+pub(crate) struct KeyboardTranspose {
+    pub(crate) enabled: bool,
+    pub(crate) root: u8,
+    pub(crate) degrees: i32,
+}
+
+impl KeyboardTranspose {
+    pub(crate) fn new() -> Self {
+        Self {
+            enabled: false,
+            root: 60,
+            degrees: 0,
+        }
+    }
+
+    pub(crate) fn note_on(&mut self, number: u8, scale: &Scale) {
+        let (root_index, root_octave) = scale.nearest_degree(self.root as f64);
+        let (note_index, octave) = scale.nearest_degree(number as f64);
+        let scale_len = scale.steps.len() as i32;
+        self.degrees = (octave as i32 - root_octave as i32) * scale_len + note_index as i32
+            - root_index as i32;
+    }
 }
 
 impl Sequencer {
 
-    pub fn new(sequences: Vec<Sequence>) -> Self {
+    pub fn new(sequences: Vec<Sequence>, scales: Vec<Arc<Scale>>) -> Self {
         Self {
             sequences,
+            scales,
             current_sequence_index: 0,
+            queued_sequence: None,
             times_repeated: 0,
             current_note_index: 0,
             started: false,
+            voices: VoiceAllocator::new(),
+            output: OutputSettings::new(),
+            arpeggiator: Arpeggiator::new(),
+            transpose: KeyboardTranspose::new(),
+            scheduler: Scheduler::new(),
+            pitch_update_rate: 200,
+            humanizer: Rng::from_time(),
+            humanized_sequence: None,
+            routing: MidiRouting::new(),
+            tempo: Tempo::new(),
+            pending_reload: None,
+            reload_count: 0,
+        }
+    }
+
+    /// A sequencer playing a single C in twelve-tone equal temperament, to start with when the
+    /// scale or sequence file can't be loaded.
+    pub(crate) fn placeholder() -> Self {
+        let note_names = ["C", "C#", "D", "D#", "E", "F", "F#", "G", "G#", "A", "A#", "B"];
+        let scale = Arc::new(Scale {
+            name: "12-TET".to_string(),
+            steps: (0..12).map(|step| step as f64).collect(),
+            note_names: note_names.iter().map(|name| name.to_string()).collect(),
+        });
+        let note = Note::new(scale.clone(), 0, 5, NoteDuration { duration: 1f64 }, 100);
+        let sequence = Sequence::new("Empty".to_string(), scale.clone(), vec![note]);
+        Self::new(vec![sequence], vec![scale])
+    }
+
+    /// Takes the scales, sequences and settings of a loaded project. The playing position is
+    /// kept: the first sequence of the project starts at the end of the current loop.
+    ///
+    /// The MIDI routing and the sync mode apply the next time the sequencer is turned on.
+    pub(crate) fn load_project(&mut self, project: Sequencer) {
+        self.take_project(project);
+        self.queue_sequence(0);
+    }
+
+    /// Puts the reloaded data in place: at the end of the current loop while playing, so that
+    /// the playing position is kept, or right away when stopped. A newer reload replaces one
+    /// still waiting.
+    pub(crate) fn reload(&mut self, reload: Reload) {
+        match self.started {
+            true => self.pending_reload = Some(reload),
+            false => self.apply_reload(reload),
+        }
+    }
+
+    pub(crate) fn reload_count(&self) -> u64 {
+        self.reload_count
+    }
+
+    fn apply_reload(&mut self, reload: Reload) {
+        self.reload_count += 1;
+        match reload {
+            Reload::Files { scales, sequences } => {
+                self.scales = scales;
+                self.replace_sequences(sequences);
+            }
+            Reload::Project(project) => self.take_project(*project),
         }
     }
 
+    /// Takes the scales, sequences and settings of `project`, keeping the playing position
+    /// where the playlist allows it.
+    fn take_project(&mut self, project: Sequencer) {
+        self.scales = project.scales;
+        self.replace_sequences(project.sequences);
+        self.output = project.output;
+        self.arpeggiator = project.arpeggiator;
+        self.transpose = project.transpose;
+        self.pitch_update_rate = project.pitch_update_rate;
+        self.routing = project.routing;
+        self.tempo = project.tempo;
+    }
+
     pub fn reset(&mut self) {
         self.current_sequence_index = 0;
         self.current_note_index = 0;
         self.times_repeated = 0;
         self.started = false;
+        self.queued_sequence = None;
+        self.humanized_sequence = None;
+        self.scheduler.cancel_note_ons();
+        if let Some(reload) = self.pending_reload.take() {
+            self.apply_reload(reload);
+        }
     }
 
-    pub fn next_note(&mut self) {
+    /// Computes where the sequencer will be after the current note, as
+    /// `(sequence index, note index, times repeated)`.
+    fn following_position(&self) -> (usize, usize, usize) {
+        let current_sequence = &self.sequences[self.current_sequence_index];
+        if let Some(queued) = self.queued_sequence {
+            if self.current_note_index >= current_sequence.notes.len() - 1 {
+                return (queued, 0, 0);
+            }
+        }
 
         // Optimization: no need to check if we need to change sequence if the sequencer has
         // only one sequence
         if self.sequences.len() == 1 {
-            self.current_note_index = (self.current_note_index + 1) % self.sequences[0].notes.len();            
-            return;
+            let note_index = (self.current_note_index + 1) % self.sequences[0].notes.len();
+            return (0, note_index, self.times_repeated);
         }
 
-        let current_sequence = &mut self.sequences[self.current_sequence_index];
+        let mut times_repeated = self.times_repeated;
 
         if self.current_note_index == current_sequence.notes.len() - 1 {
-            self.times_repeated += 1;
+            times_repeated += 1;
+        }
+        if times_repeated > current_sequence.repeat {
+            ((self.current_sequence_index + 1) % self.sequences.len(), 0, 0)
+        }
+        else {
+            let note_index = (self.current_note_index + 1) % current_sequence.notes.len();
+            (self.current_sequence_index, note_index, times_repeated)
+        }
+    }
+
+    pub fn next_note(&mut self) {
+        (self.current_sequence_index, self.current_note_index, self.times_repeated) =
+            self.following_position();
+        if self.current_note_index == 0 && self.queued_sequence == Some(self.current_sequence_index)
+        {
+            self.queued_sequence = None;
+        }
+
+        if self.current_note_index == 0 {
+            if let Some(reload) = self.pending_reload.take() {
+                self.apply_reload(reload);
+            }
+            let sequence = &mut self.sequences[self.current_sequence_index];
+            if let Some(generator) = &mut sequence.generator {
+                if generator.loop_started() {
+                    generator.generate(&mut sequence.notes);
+                }
+            }
         }
-        if self.times_repeated > current_sequence.repeat {
+    }
+
+    /// Puts a sequence in place of the one at `index`, if there is one. If it is playing and
+    /// gets shorter, it continues from its first note.
+    pub(crate) fn replace_sequence(&mut self, index: usize, sequence: Sequence) {
+        let Some(replaced) = self.sequences.get_mut(index) else {
+            return;
+        };
+        *replaced = sequence;
+        if index == self.current_sequence_index
+            && self.current_note_index >= self.sequences[index].notes.len()
+        {
+            self.current_note_index = 0;
+        }
+    }
+
+    /// Puts a new playlist in place of the sequences. If the playing sequence is not in it
+    /// anymore, the last one plays from its start.
+    pub(crate) fn replace_sequences(&mut self, sequences: Vec<Sequence>) {
+        self.sequences = sequences;
+        let last = self.sequences.len() - 1;
+        if self.current_sequence_index > last {
+            self.current_sequence_index = last;
+            self.current_note_index = 0;
             self.times_repeated = 0;
-            self.current_sequence_index = (self.current_sequence_index + 1) % self.sequences.len();
+        }
+        if self.current_note_index >= self.sequences[self.current_sequence_index].notes.len() {
             self.current_note_index = 0;
         }
-        else {
-            self.current_note_index = (self.current_note_index + 1) % current_sequence.notes.len();
+        self.queued_sequence = self.queued_sequence.filter(|i| *i <= last);
+        self.humanized_sequence = None;
+    }
+
+    /// Plays the sequence `index` from its start: at the end of the current loop while playing,
+    /// or right away when stopped.
+    pub(crate) fn queue_sequence(&mut self, index: usize) {
+        if self.started {
+            self.queued_sequence = Some(index);
+        } else {
+            self.current_sequence_index = index;
+            self.current_note_index = 0;
+            self.times_repeated = 0;
+        }
+    }
+
+    /// Swaps two sequences of the playlist. The playing and queued sequences stay the same.
+    pub(crate) fn swap_sequences(&mut self, a: usize, b: usize) {
+        self.sequences.swap(a, b);
+        self.current_sequence_index = swapped_index(self.current_sequence_index, a, b);
+        self.queued_sequence = self.queued_sequence.map(|i| swapped_index(i, a, b));
+        self.humanized_sequence = None;
+    }
+
+    pub(crate) fn insert_sequence(&mut self, index: usize, sequence: Sequence) {
+        self.sequences.insert(index, sequence);
+        if self.current_sequence_index >= index {
+            self.current_sequence_index += 1;
+        }
+        self.queued_sequence = self.queued_sequence.map(|i| if i >= index { i + 1 } else { i });
+        self.humanized_sequence = None;
+    }
+
+    /// Removes a sequence from the playlist, unless it is the last one. When it is playing,
+    /// the sequence taking its place starts from its first note.
+    pub(crate) fn remove_sequence(&mut self, index: usize) -> Option<Sequence> {
+        if self.sequences.len() <= 1 {
+            return None;
+        }
+        let removed = self.sequences.remove(index);
+        if self.current_sequence_index == index {
+            self.current_sequence_index = index.min(self.sequences.len() - 1);
+            self.current_note_index = 0;
+            self.times_repeated = 0;
+        } else if self.current_sequence_index > index {
+            self.current_sequence_index -= 1;
+        }
+        self.queued_sequence = match self.queued_sequence {
+            Some(i) if i == index => None,
+            Some(i) if i > index => Some(i - 1),
+            queued => queued,
+        };
+        self.humanized_sequence = None;
+        Some(removed)
+    }
+
+    /// Puts an edited scale in the loaded scales, at `index` or at the end for a new one, and
+    /// moves the sequences using it to the new version, degree for degree. Returns its index.
+    pub(crate) fn replace_scale(&mut self, index: Option<usize>, scale: Scale) -> usize {
+        let scale = Arc::new(scale);
+        match index {
+            Some(i) => {
+                let old_name = self.scales[i].name.clone();
+                self.scales[i] = scale.clone();
+                for sequence in &mut self.sequences {
+                    if sequence.scale.name == old_name {
+                        sequence.retune(scale.clone(), RetuneMode::Degree);
+                    }
+                }
+                i
+            }
+            None => {
+                self.scales.push(scale);
+                self.scales.len() - 1
+            }
         }
     }
 
     pub fn current_note(&self) -> Note {
         self.sequences[self.current_sequence_index].notes[self.current_note_index].clone()
     }
+
+    /// The current note, as it should be played once the keyboard transpose and the
+    /// humanization are applied.
+    ///
+    /// Each call draws new random deviations, so it should be called once per played note.
+    pub fn current_played_note(&mut self) -> Note {
+        self.played_note(self.current_sequence_index, self.current_note())
+    }
+
+    /// Whether the upcoming note can't be known before `next_note` moves to it: it starts a
+    /// loop and a reload is waiting for it. The melody generator keeps the first note of its
+    /// loops, so it doesn't change the upcoming note.
+    pub(crate) fn upcoming_note_may_change(&self) -> bool {
+        self.pending_reload.is_some() && self.following_position().1 == 0
+    }
+
+    /// The note that will follow the current one, as it should be played.
+    pub fn upcoming_played_note(&mut self) -> Note {
+        let (sequence_index, note_index, _) = self.following_position();
+        let note = self.sequences[sequence_index].notes[note_index].clone();
+        self.played_note(sequence_index, note)
+    }
+
+    fn played_note(&mut self, sequence_index: usize, note: Note) -> Note {
+        let mut note = if self.transpose.enabled {
+            note.transposed(self.transpose.degrees)
+        } else {
+            note
+        };
+        let humanize = self.sequences[sequence_index].humanize;
+        if self.humanized_sequence != Some(sequence_index) {
+            self.humanizer = humanize.rng();
+            self.humanized_sequence = Some(sequence_index);
+        }
+        humanize.apply(&mut note, &mut self.humanizer);
+        note
+    }
     
     pub fn current_sequence(&self) -> Sequence {
         self.sequences[self.current_sequence_index].clone()
     }
     
+    pub fn current_scale(&self) -> Arc<Scale> {
+        self.sequences[self.current_sequence_index].scale.clone()
+    }
+
     pub fn current_sequence_name(&self) -> String {
         self.sequences[self.current_sequence_index].name.clone()
     }
@@ -121,4 +607,280 @@ impl Sequencer {
     pub fn current_sequence_length(&self) -> usize {
         self.sequences[self.current_sequence_index].notes.len()
     }
-}
\ No newline at end of file
+}
+
+/// Where a sequence at `index` goes when the sequences `a` and `b` are swapped.
+fn swapped_index(index: usize, a: usize, b: usize) -> usize {
+    match index {
+        i if i == a => b,
+        i if i == b => a,
+        i => i,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn reload_with_note_index(sequencer: &Sequencer, note_index: usize) -> Reload {
+        let mut sequence = sequencer.sequences[0].clone();
+        for note in &mut sequence.notes {
+            note.note_index = note_index;
+        }
+        Reload::Files {
+            scales: sequencer.scales.clone(),
+            sequences: vec![sequence],
+        }
+    }
+
+    fn playing_sequencer(note_count: usize) -> Sequencer {
+        let mut sequencer = Sequencer::placeholder();
+        let note = sequencer.sequences[0].notes[0].clone();
+        sequencer.sequences[0].notes = vec![note; note_count];
+        sequencer.started = true;
+        sequencer
+    }
+
+    /// A sequence in 12-TET with notes at the given degrees.
+    fn melody(degrees: &[i32]) -> Sequence {
+        let mut sequence = Sequencer::placeholder().sequences.remove(0);
+        let note = sequence.notes[0].clone();
+        sequence.notes = degrees
+            .iter()
+            .map(|degree| {
+                let mut note = note.clone();
+                note.set_degree(*degree);
+                note
+            })
+            .collect();
+        sequence
+    }
+
+    fn degrees(sequence: &Sequence) -> Vec<i32> {
+        sequence.notes.iter().map(|note| note.degree()).collect()
+    }
+
+    #[test]
+    fn transpose_and_invert_move_the_degrees() {
+        let mut sequence = melody(&[60, 62, 67]);
+        sequence.transpose(-2);
+        assert_eq!(degrees(&sequence), vec![58, 60, 65]);
+        sequence.invert(60);
+        assert_eq!(degrees(&sequence), vec![62, 60, 55]);
+    }
+
+    #[test]
+    fn retrograde_and_rotate_reorder_the_notes() {
+        let mut sequence = melody(&[60, 62, 64, 65]);
+        sequence.retrograde();
+        assert_eq!(degrees(&sequence), vec![65, 64, 62, 60]);
+        sequence.rotate(1);
+        assert_eq!(degrees(&sequence), vec![60, 65, 64, 62]);
+        sequence.rotate(-5);
+        assert_eq!(degrees(&sequence), vec![65, 64, 62, 60]);
+    }
+
+    #[test]
+    fn scale_durations_stays_within_the_clock() {
+        let mut sequence = melody(&[60, 62]);
+        sequence.notes[1].duration.duration = 0.25;
+        sequence.notes[1].gate = Gate::Beats(0.125);
+        sequence.scale_durations(2f64);
+        assert_eq!(sequence.notes[0].duration.duration, 2f64);
+        assert_eq!(sequence.notes[1].duration.duration, 0.5);
+        assert!(sequence.notes[1].gate == Gate::Beats(0.25));
+
+        sequence.scale_durations(16f64);
+        assert_eq!(sequence.notes[0].duration.duration, MAX_NOTE_BEATS);
+        sequence.scale_durations(0.001);
+        assert_eq!(sequence.notes[1].duration.duration, 1f64 / 24f64);
+    }
+
+    #[test]
+    fn fold_octaves_brings_the_notes_into_the_range() {
+        let mut sequence = melody(&[40, 61, 90]);
+        sequence.fold_octaves(60, 72);
+        assert_eq!(degrees(&sequence), vec![64, 61, 66]);
+    }
+
+    #[test]
+    fn reload_waits_for_the_end_of_the_loop() {
+        let mut sequencer = playing_sequencer(3);
+        sequencer.next_note();
+        let reload = reload_with_note_index(&sequencer, 4);
+        sequencer.reload(reload);
+
+        assert!(!sequencer.upcoming_note_may_change());
+        sequencer.next_note();
+        assert_eq!(sequencer.current_note().note_index, 0);
+        assert!(sequencer.upcoming_note_may_change());
+        sequencer.next_note();
+        assert_eq!(sequencer.current_note_index, 0);
+        assert_eq!(sequencer.current_played_note().note_index, 4);
+        assert!(!sequencer.upcoming_note_may_change());
+    }
+
+    #[test]
+    fn reload_applies_right_away_when_stopped() {
+        let mut sequencer = playing_sequencer(3);
+        sequencer.started = false;
+        let reload = reload_with_note_index(&sequencer, 2);
+        sequencer.reload(reload);
+        assert_eq!(sequencer.current_note().note_index, 2);
+        assert_eq!(sequencer.reload_count(), 1);
+    }
+
+    fn scale(name: &str, steps: Vec<f64>) -> Scale {
+        Scale {
+            name: name.to_string(),
+            note_names: steps.iter().map(|step| step.to_string()).collect(),
+            steps,
+        }
+    }
+
+    fn midi_numbers(sequence: &Sequence) -> Vec<f64> {
+        sequence.notes.iter().map(|note| note.get_midi_number()).collect()
+    }
+
+    #[test]
+    fn nearest_pitch_retuning_reports_the_cents_moved() {
+        let mut sequence = melody(&[60, 65, 71]);
+        let pentatonic = scale("Pentatonic", vec![0f64, 2f64, 4f64, 7f64, 9f64]);
+        let errors = sequence.retune(Arc::new(pentatonic), RetuneMode::NearestPitch);
+        // 71 snaps up to the root of the next octave
+        assert_eq!(midi_numbers(&sequence), vec![60f64, 64f64, 72f64]);
+        assert_eq!(errors, vec![0f64, -100f64, 100f64]);
+    }
+
+    #[test]
+    fn degree_retuning_keeps_the_degrees_of_a_scale_of_the_same_size() {
+        let mut sequence = melody(&[60, 67]);
+        let shifted = scale("Shifted", (0..12).map(|step| step as f64 + 0.5).collect());
+        let errors = sequence.retune(Arc::new(shifted), RetuneMode::Degree);
+        assert_eq!(degrees(&sequence), vec![60, 67]);
+        assert_eq!(errors, vec![50f64, 50f64]);
+    }
+
+    #[test]
+    fn degree_retuning_keeps_the_place_in_the_octave_of_a_scale_of_another_size() {
+        let mut sequence = melody(&[60, 66, 71]);
+        let pentatonic = scale("Pentatonic", vec![0f64, 2f64, 4f64, 7f64, 9f64]);
+        let errors = sequence.retune(Arc::new(pentatonic), RetuneMode::Degree);
+        assert_eq!(midi_numbers(&sequence), vec![60f64, 67f64, 69f64]);
+        assert_eq!(errors, vec![0f64, 100f64, -200f64]);
+    }
+
+    #[test]
+    fn notes_past_the_midi_range_are_clamped_and_reported() {
+        let mut sequence = melody(&[120]);
+        let high = scale("High", (0..12).map(|step| step as f64 + 24f64).collect());
+        let errors = sequence.retune(Arc::new(high), RetuneMode::Degree);
+        assert_eq!(midi_numbers(&sequence), vec![127f64]);
+        assert_eq!(errors, vec![700f64]);
+    }
+
+    #[test]
+    fn replace_scale_retunes_only_the_sequences_using_it() {
+        let mut sequencer = Sequencer::placeholder();
+        let other = Arc::new(scale("Other", (0..12).map(|step| step as f64).collect()));
+        let mut note = sequencer.sequences[0].notes[0].clone();
+        note.scale = other.clone();
+        sequencer.scales.push(other.clone());
+        sequencer.insert_sequence(1, Sequence::new("Other".to_string(), other, vec![note]));
+
+        let edited = scale("12-TET", (0..12).map(|step| step as f64 + 0.25).collect());
+        assert_eq!(sequencer.replace_scale(Some(0), edited), 0);
+        assert_eq!(midi_numbers(&sequencer.sequences[0]), vec![60.25]);
+        assert_eq!(midi_numbers(&sequencer.sequences[1]), vec![60f64]);
+
+        let new = scale("New", vec![0f64]);
+        assert_eq!(sequencer.replace_scale(None, new), 2);
+        assert_eq!(sequencer.scales[2].name, "New");
+    }
+
+    /// A playing sequencer with sequences named "0", "1"... of two notes each.
+    fn playlist(sequence_count: usize) -> Sequencer {
+        let mut sequencer = playing_sequencer(2);
+        let sequence = sequencer.sequences[0].clone();
+        sequencer.sequences = (0..sequence_count)
+            .map(|i| Sequence { name: i.to_string(), ..sequence.clone() })
+            .collect();
+        sequencer
+    }
+
+    fn names(sequencer: &Sequencer) -> Vec<&str> {
+        sequencer.sequences.iter().map(|sequence| sequence.name.as_str()).collect()
+    }
+
+    fn playing_and_queued(sequencer: &Sequencer) -> (&str, Option<&str>) {
+        let queued = sequencer.queued_sequence.map(|i| sequencer.sequences[i].name.as_str());
+        (sequencer.sequences[sequencer.current_sequence_index].name.as_str(), queued)
+    }
+
+    #[test]
+    fn inserting_keeps_the_playing_and_queued_sequences() {
+        let mut sequencer = playlist(3);
+        sequencer.current_sequence_index = 1;
+        sequencer.queue_sequence(2);
+        let new = Sequence { name: "new".to_string(), ..sequencer.sequences[0].clone() };
+        sequencer.insert_sequence(1, new.clone());
+        sequencer.insert_sequence(4, new);
+        assert_eq!(names(&sequencer), vec!["0", "new", "1", "2", "new"]);
+        assert_eq!(playing_and_queued(&sequencer), ("1", Some("2")));
+    }
+
+    #[test]
+    fn removing_the_playing_sequence_starts_the_next_one_from_its_start() {
+        let mut sequencer = playlist(3);
+        sequencer.current_sequence_index = 1;
+        sequencer.current_note_index = 1;
+        sequencer.queue_sequence(2);
+        assert_eq!(sequencer.remove_sequence(1).map(|sequence| sequence.name), Some("1".into()));
+        assert_eq!(playing_and_queued(&sequencer), ("2", Some("2")));
+        assert_eq!(sequencer.current_note_index, 0);
+
+        // The last sequence of the playlist is replaced by the one before it
+        sequencer.remove_sequence(1);
+        assert_eq!(playing_and_queued(&sequencer), ("0", None));
+        assert!(sequencer.remove_sequence(0).is_none());
+    }
+
+    #[test]
+    fn removing_the_queued_sequence_cancels_it() {
+        let mut sequencer = playlist(4);
+        sequencer.current_sequence_index = 3;
+        sequencer.queue_sequence(2);
+        sequencer.remove_sequence(0);
+        assert_eq!(playing_and_queued(&sequencer), ("3", Some("2")));
+        sequencer.remove_sequence(1);
+        assert_eq!(playing_and_queued(&sequencer), ("3", None));
+        assert_eq!(names(&sequencer), vec!["1", "3"]);
+    }
+
+    #[test]
+    fn swapping_moves_the_playing_and_queued_sequences_along() {
+        let mut sequencer = playlist(3);
+        sequencer.queue_sequence(2);
+        sequencer.swap_sequences(0, 2);
+        assert_eq!(names(&sequencer), vec!["2", "1", "0"]);
+        assert_eq!(playing_and_queued(&sequencer), ("0", Some("2")));
+        assert_eq!((sequencer.current_sequence_index, sequencer.queued_sequence), (2, Some(0)));
+    }
+
+    #[test]
+    fn queued_sequences_start_at_the_end_of_the_loop() {
+        let mut sequencer = playlist(3);
+        sequencer.queue_sequence(2);
+        sequencer.next_note();
+        assert_eq!(playing_and_queued(&sequencer), ("0", Some("2")));
+        sequencer.next_note();
+        assert_eq!(playing_and_queued(&sequencer), ("2", None));
+        assert_eq!(sequencer.current_note_index, 0);
+
+        sequencer.started = false;
+        sequencer.current_note_index = 1;
+        sequencer.queue_sequence(1);
+        assert_eq!(playing_and_queued(&sequencer), ("1", None));
+        assert_eq!(sequencer.current_note_index, 0);
+    }
+}
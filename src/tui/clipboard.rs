@@ -0,0 +1,47 @@
+use crate::note::{Note, Scale};
+use crate::sequencer::{retune_note, RetuneMode, Sequence};
+use std::sync::Arc;
+
+/// What was last copied or cut in the sequencer screen. It is kept by the TUI only, and can
+/// be pasted in any sequence.
+pub(crate) enum Clipboard {
+    /// Steps copied from the note grid.
+    Steps(Vec<Note>),
+    /// A sequence copied from the playlist.
+    Sequence(Sequence),
+}
+
+impl Clipboard {
+    pub(crate) fn notes(&self) -> &[Note] {
+        match self {
+            Clipboard::Steps(notes) => notes,
+            Clipboard::Sequence(sequence) => &sequence.notes,
+        }
+    }
+
+    /// The copied notes, moved into `scale` if they were copied from a sequence using another
+    /// scale. Notes of a scale with the same name keep their degrees, in case the scale was
+    /// edited since they were copied.
+    pub(crate) fn notes_in(&self, scale: &Arc<Scale>, mode: RetuneMode) -> Vec<Note> {
+        let mut notes = self.notes().to_vec();
+        for note in &mut notes {
+            let mode = match note.scale.name == scale.name {
+                true => RetuneMode::Degree,
+                false => mode,
+            };
+            retune_note(note, scale, mode);
+        }
+        notes
+    }
+
+    /// The copied sequence, or a new sequence made of the copied steps.
+    pub(crate) fn to_sequence(&self) -> Sequence {
+        match self {
+            Clipboard::Steps(notes) => {
+                let name = "Pasted steps".to_string();
+                Sequence::new(name, notes[0].scale.clone(), notes.clone())
+            }
+            Clipboard::Sequence(sequence) => sequence.clone(),
+        }
+    }
+}
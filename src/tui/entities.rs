@@ -3,13 +3,21 @@ use std::io;
 use std::sync::atomic::AtomicBool;
 use std::sync::{Arc, Mutex};
 use crossterm::event::KeyCode;
-use crate::sequencer::Sequencer;
+use crate::history::History;
+use crate::paths::DataPaths;
+use crate::sequencer::{RetuneMode, Sequencer};
+use crate::tui::clipboard::Clipboard;
+use crate::tui::file_watcher::FileWatcher;
+use crate::tui::menus::file_browser::FileBrowser;
+use crate::tui::menus::scale_editor::ScaleEditor;
 
 pub enum Menu {
     Main { selected_menu: MainMenuItem },
     Sequencer { selected_menu: SequencerMenuSelectedItem } ,
+    // The link controller screen is not implemented yet
+    #[allow(dead_code)]
     LinkController,
-    Settings
+    Settings { selected_menu: SettingsMenuItem },
 }
 
 pub trait MenuItemList {
@@ -25,11 +33,27 @@ pub enum MainMenuItem {
     Exit,
 }
 
+pub enum SettingsMenuItem {
+    ArpMode,
+    ArpRate,
+    Transpose,
+    TransposeRoot,
+    OutputMode,
+    VelocityScale,
+    VelocityCurve,
+    PitchUpdateRate,
+    Sync,
+    Tempo,
+    Exit,
+}
+
 #[derive(Clone)]
 pub enum SequencerMenuItem {
     OnOff,
     Scale,
     Save,
+    SaveAs,
+    SaveProjectAs,
     Load,
     Exit,
 }
@@ -38,8 +62,13 @@ pub enum SequencerMenuItem {
 pub enum SequencerMenuSelectedItem {
     SubMenuItem { item: SequencerMenuItem },
     Note { item: usize },
+    Inspector { item: usize, field: usize },
     PlaylistItem { item: usize },
     Scale { item: usize },
+    ScaleEditor { editor: ScaleEditor },
+    /// The path typed to save the sequences, or the whole project, to.
+    SaveAs { path: String, project: bool },
+    FileBrowser { browser: FileBrowser },
 }
 
 impl SequencerMenuSelectedItem {
@@ -73,14 +102,55 @@ impl MenuItemList for MainMenuItem {
     }
 }
 
+impl MenuItemList for SettingsMenuItem {
+    fn as_index(&self) -> usize {
+        match self {
+            SettingsMenuItem::ArpMode => 0,
+            SettingsMenuItem::ArpRate => 1,
+            SettingsMenuItem::Transpose => 2,
+            SettingsMenuItem::TransposeRoot => 3,
+            SettingsMenuItem::OutputMode => 4,
+            SettingsMenuItem::VelocityScale => 5,
+            SettingsMenuItem::VelocityCurve => 6,
+            SettingsMenuItem::PitchUpdateRate => 7,
+            SettingsMenuItem::Sync => 8,
+            SettingsMenuItem::Tempo => 9,
+            SettingsMenuItem::Exit => 10,
+        }
+    }
+
+    fn from_index(index: usize) -> Self {
+        match index {
+            0 => SettingsMenuItem::ArpMode,
+            1 => SettingsMenuItem::ArpRate,
+            2 => SettingsMenuItem::Transpose,
+            3 => SettingsMenuItem::TransposeRoot,
+            4 => SettingsMenuItem::OutputMode,
+            5 => SettingsMenuItem::VelocityScale,
+            6 => SettingsMenuItem::VelocityCurve,
+            7 => SettingsMenuItem::PitchUpdateRate,
+            8 => SettingsMenuItem::Sync,
+            9 => SettingsMenuItem::Tempo,
+            10 => SettingsMenuItem::Exit,
+            _ => SettingsMenuItem::ArpMode, // fallback
+        }
+    }
+
+    fn length() -> usize {
+        SettingsMenuItem::Exit.as_index() + 1
+    }
+}
+
 impl MenuItemList for SequencerMenuItem {
     fn as_index(&self) -> usize {
         match self {
             SequencerMenuItem::OnOff => 0,
             SequencerMenuItem::Scale => 1,
             SequencerMenuItem::Save => 2,
-            SequencerMenuItem::Load => 3,
-            SequencerMenuItem::Exit => 4,
+            SequencerMenuItem::SaveAs => 3,
+            SequencerMenuItem::SaveProjectAs => 4,
+            SequencerMenuItem::Load => 5,
+            SequencerMenuItem::Exit => 6,
         }
     }
 
@@ -89,8 +159,10 @@ impl MenuItemList for SequencerMenuItem {
             0 => SequencerMenuItem::OnOff,
             1 => SequencerMenuItem::Scale,
             2 => SequencerMenuItem::Save,
-            3 => SequencerMenuItem::Load,
-            4 => SequencerMenuItem::Exit,
+            3 => SequencerMenuItem::SaveAs,
+            4 => SequencerMenuItem::SaveProjectAs,
+            5 => SequencerMenuItem::Load,
+            6 => SequencerMenuItem::Exit,
             _ => SequencerMenuItem::OnOff, // fallback
         }
     }
@@ -106,11 +178,25 @@ pub struct App {
     pub(crate) current_menu: Menu,
     pub(crate) sequencer: Arc<Mutex<Sequencer>>,
     pub(crate) error: Option<io::Error>,
-    pub(crate) held_keys: HashSet<KeyCode>
+    pub(crate) held_keys: HashSet<KeyCode>,
+    /// Cents error of each note after the last retune, with the index of the retuned sequence.
+    pub(crate) retune_errors: Option<(usize, Vec<f64>)>,
+    /// How the scale browser moves the notes into the chosen scale.
+    pub(crate) retune_mode: RetuneMode,
+    pub(crate) history: History,
+    /// The other end of the steps selected in the note grid, when more than one is selected.
+    pub(crate) selection_anchor: Option<usize>,
+    pub(crate) clipboard: Option<Clipboard>,
+    /// Files the scales and sequences are saved to.
+    pub(crate) scale_path: String,
+    pub(crate) sequence_path: String,
+    /// The project file loaded or last saved, which "Save" then writes everything to.
+    pub(crate) project_path: Option<String>,
+    pub(crate) file_watcher: FileWatcher,
 }
 
 impl App {
-    pub fn new(sequencer: Arc<Mutex<Sequencer>>) -> Self {
+    pub fn new(sequencer: Arc<Mutex<Sequencer>>, paths: DataPaths) -> Self {
         App {
             tui_on: AtomicBool::new(true),
             sequencer_on: Arc::new(AtomicBool::new(false)),
@@ -119,7 +205,16 @@ impl App {
             },
             sequencer,
             error: None,
-            held_keys: HashSet::new()
+            held_keys: HashSet::new(),
+            retune_errors: None,
+            retune_mode: RetuneMode::NearestPitch,
+            history: History::new(),
+            selection_anchor: None,
+            clipboard: None,
+            scale_path: paths.scale_path,
+            sequence_path: paths.sequence_path,
+            project_path: paths.project_path,
+            file_watcher: FileWatcher::new(),
         }
     }
 }
\ No newline at end of file
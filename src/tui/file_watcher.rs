@@ -0,0 +1,122 @@
+use crate::json::{get_sequencer_from_json, get_sequencer_from_project_file};
+use crate::paths::DataSource;
+use crate::sequencer::Reload;
+use crate::tui::entities::App;
+use crate::tui::menus::sequencer_menu;
+use std::fs;
+use std::io;
+use std::time::{Duration, Instant, SystemTime};
+
+/// Time between two checks of the files.
+const CHECK_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Polls the modification times of the loaded files, to reload them when they are edited in
+/// another program.
+pub(crate) struct FileWatcher {
+    files: Vec<(String, Option<SystemTime>)>,
+    last_check: Instant,
+}
+
+impl FileWatcher {
+    pub(crate) fn new() -> Self {
+        Self {
+            files: vec![],
+            last_check: Instant::now(),
+        }
+    }
+
+    /// Takes the modification times of `paths` as they are now, so that only later changes
+    /// count.
+    fn watch(&mut self, paths: Vec<String>) {
+        self.files = paths
+            .into_iter()
+            .map(|path| {
+                let modified = modified(&path);
+                (path, modified)
+            })
+            .collect();
+    }
+
+    /// Whether one of `paths` was modified since the last call. Changing the watched files,
+    /// when others are loaded or saved to, isn't a modification.
+    ///
+    /// A file that disappears isn't reloaded: editors often replace files by deleting them
+    /// first. It is once it is written again.
+    fn changed(&mut self, paths: Vec<String>) -> bool {
+        let watched: Vec<&String> = self.files.iter().map(|(path, _)| path).collect();
+        if watched != paths.iter().collect::<Vec<_>>() {
+            self.watch(paths);
+            return false;
+        }
+        let mut changed = false;
+        for (path, last_modified) in &mut self.files {
+            let modified = modified(path);
+            if modified.is_some() && modified != *last_modified {
+                changed = true;
+            }
+            *last_modified = modified;
+        }
+        changed
+    }
+}
+
+fn modified(path: &str) -> Option<SystemTime> {
+    fs::metadata(path).and_then(|metadata| metadata.modified()).ok()
+}
+
+/// The files the sequencer was loaded from: the project, or the scale and sequence files.
+fn loaded_paths(app: &App) -> Vec<String> {
+    match &app.project_path {
+        Some(path) => vec![path.clone()],
+        None => vec![app.scale_path.clone(), app.sequence_path.clone()],
+    }
+}
+
+/// To call after the TUI writes the files, so that they aren't reloaded.
+pub(crate) fn saved(app: &mut App) {
+    let paths = loaded_paths(app);
+    app.file_watcher.watch(paths);
+}
+
+/// Reloads the files if they were modified. They are put in place at the end of the playing
+/// loop, replacing the edits made in the TUI since. The edits made before can't be undone
+/// anymore once they are.
+///
+/// Files that can't be loaded are reported and the sequencer keeps playing what it has.
+pub(crate) fn check_files(app: &mut App) -> Result<(), io::Error> {
+    if app.file_watcher.last_check.elapsed() < CHECK_INTERVAL {
+        return Ok(());
+    }
+    app.file_watcher.last_check = Instant::now();
+    let paths = loaded_paths(app);
+    if !app.file_watcher.changed(paths) {
+        return Ok(());
+    }
+
+    let (reload, warnings) = match &app.project_path {
+        Some(path) => {
+            let (project, warnings) = get_sequencer_from_project_file(path)?;
+            (Reload::Project(Box::new(project)), warnings)
+        }
+        None => {
+            let (loaded, warnings) = get_sequencer_from_json(
+                &DataSource::saved_scales(&app.scale_path),
+                &DataSource::saved_sequences(&app.sequence_path),
+            )?;
+            let reload = Reload::Files {
+                scales: loaded.scales,
+                sequences: loaded.sequences,
+            };
+            (reload, warnings)
+        }
+    };
+    app.sequencer.lock().unwrap().reload(reload);
+    sequencer_menu::keep_selection_in_range(app);
+    match warnings.is_empty() {
+        true => Ok(()),
+        false => Err(io::Error::other(format!(
+            "Reloaded with warnings:\n{}",
+            warnings
+        ))),
+    }
+}
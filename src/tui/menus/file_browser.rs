@@ -0,0 +1,342 @@
+use crate::history::EditCommand;
+use crate::json::{
+    build_scales_from_json_scale_file, build_sequencer_from_json_project_file,
+    build_sequences_from_json_sequence_file, read_data_file, JsonDataFile, JsonProjectFile,
+    JsonScaleFile, JsonSequenceFile, JsonSyncMode,
+};
+use crate::note::Scale;
+use crate::tui::entities::{App, Menu, SequencerMenuItem, SequencerMenuSelectedItem};
+use crate::validate::{check, validate_scales, validate_sequences};
+use crossterm::event::{KeyCode, KeyEvent, KeyEventKind};
+use ratatui::layout::{Constraint, Direction, Layout, Rect};
+use ratatui::prelude::{Color, Line, Style};
+use ratatui::widgets::{Block, Clear, List, ListItem, ListState, Paragraph, Wrap};
+use ratatui::Frame;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+#[derive(Clone)]
+struct Entry {
+    path: PathBuf,
+    label: String,
+    is_dir: bool,
+}
+
+/// A dialog listing the folders and JSON files of a folder, with a preview of the selected
+/// file.
+#[derive(Clone)]
+pub struct FileBrowser {
+    dir: PathBuf,
+    entries: Vec<Entry>,
+    item: usize,
+    preview: Vec<String>,
+}
+
+fn get_selected(app: &App) -> Option<FileBrowser> {
+    match &app.current_menu {
+        Menu::Sequencer {
+            selected_menu: SequencerMenuSelectedItem::FileBrowser { browser },
+        } => Some(browser.clone()),
+        _ => None,
+    }
+}
+
+fn set_selected(app: &mut App, browser: FileBrowser) {
+    app.current_menu = Menu::Sequencer {
+        selected_menu: SequencerMenuSelectedItem::FileBrowser { browser },
+    };
+}
+
+pub fn draw(frame: &mut Frame, app: &App, area: Rect) {
+    let Some(browser) = get_selected(app) else {
+        return;
+    };
+
+    let width = 90.min(area.width);
+    let height = 20.min(area.height);
+    let popup = Rect::new(
+        area.left() + (area.width - width) / 2,
+        area.top() + (area.height - height) / 2,
+        width,
+        height,
+    );
+    let block = Block::bordered()
+        .style(Style::default().fg(Color::White))
+        .title(Line::from(format!(" Load {} ", browser.dir.display())).centered())
+        .title_bottom(Line::from(" Enter open/replace  a append  ← parent  Esc close "));
+    let inner = block.inner(popup);
+    let columns = Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints([Constraint::Percentage(45), Constraint::Percentage(55)].as_ref())
+        .split(inner);
+
+    let items: Vec<ListItem> = browser
+        .entries
+        .iter()
+        .map(|entry| ListItem::new(entry.label.clone()))
+        .collect();
+    let list = List::new(items)
+        .style(Style::default().fg(Color::LightBlue))
+        .highlight_style(Style::default().fg(Color::Black).bg(Color::LightBlue));
+    let mut state = ListState::default();
+    state.select(Some(browser.item));
+
+    let preview = Paragraph::new(browser.preview.join("\n"))
+        .block(Block::bordered().title(Line::from(" Preview ")))
+        .wrap(Wrap { trim: false });
+
+    frame.render_widget(Clear, popup);
+    frame.render_widget(block, popup);
+    frame.render_stateful_widget(list, columns[0], &mut state);
+    frame.render_widget(preview, columns[1]);
+}
+
+pub fn handle_key(app: &mut App, key_event: KeyEvent) -> Result<(), io::Error> {
+    if key_event.kind != KeyEventKind::Press {
+        return Ok(());
+    }
+    let mut browser = get_selected(app).ok_or(io::Error::other("Out of bounds"))?;
+    let entry = browser.entries.get(browser.item).cloned();
+    match key_event.code {
+        KeyCode::Esc => close(app),
+        KeyCode::Up => {
+            browser.item = browser.item.saturating_sub(1);
+            update_preview(app, &mut browser);
+            set_selected(app, browser);
+        }
+        KeyCode::Down => {
+            browser.item = (browser.item + 1).min(browser.entries.len().saturating_sub(1));
+            update_preview(app, &mut browser);
+            set_selected(app, browser);
+        }
+        KeyCode::Left | KeyCode::Backspace => {
+            if let Some(parent) = browser.dir.parent() {
+                let parent = parent.to_path_buf();
+                set_selected(app, browse(app, parent)?);
+            }
+        }
+        KeyCode::Enter => match entry {
+            Some(entry) if entry.is_dir => set_selected(app, browse(app, entry.path)?),
+            Some(entry) => return load(app, &entry.path, false),
+            None => {}
+        },
+        KeyCode::Char('a') => match entry {
+            Some(entry) if !entry.is_dir => return load(app, &entry.path, true),
+            _ => {}
+        },
+        _ => {}
+    }
+    Ok(())
+}
+
+/// Opens the dialog in the folder of the project file, or of the sequence file.
+pub fn open(app: &mut App) -> Result<(), io::Error> {
+    let path = app.project_path.as_ref().unwrap_or(&app.sequence_path);
+    let dir = match Path::new(path).parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent.to_path_buf(),
+        _ => PathBuf::from("."),
+    };
+    let dir = dir.canonicalize().unwrap_or(dir);
+    let browser = browse(app, dir)?;
+    set_selected(app, browser);
+    Ok(())
+}
+
+fn close(app: &mut App) {
+    app.current_menu = Menu::Sequencer {
+        selected_menu: SequencerMenuSelectedItem::SubMenuItem {
+            item: SequencerMenuItem::Load,
+        },
+    };
+}
+
+/// Lists the folders, then the JSON files of `dir`. Hidden files are left out.
+fn browse(app: &App, dir: PathBuf) -> Result<FileBrowser, io::Error> {
+    let mut dirs = vec![];
+    let mut files = vec![];
+    for dir_entry in fs::read_dir(&dir)? {
+        let path = dir_entry?.path();
+        let name = path.file_name().unwrap_or_default().to_string_lossy().to_string();
+        if name.starts_with('.') {
+            continue;
+        }
+        if path.is_dir() {
+            dirs.push(Entry {
+                path,
+                label: format!("{}/", name),
+                is_dir: true,
+            });
+        } else if path.extension().is_some_and(|extension| extension == "json") {
+            files.push(Entry {
+                path,
+                label: name,
+                is_dir: false,
+            });
+        }
+    }
+    dirs.sort_by(|a, b| a.label.cmp(&b.label));
+    files.sort_by(|a, b| a.label.cmp(&b.label));
+
+    let mut entries = vec![];
+    if let Some(parent) = dir.parent() {
+        entries.push(Entry {
+            path: parent.to_path_buf(),
+            label: "../".to_string(),
+            is_dir: true,
+        });
+    }
+    entries.extend(dirs);
+    entries.extend(files);
+
+    let mut browser = FileBrowser {
+        dir,
+        entries,
+        item: 0,
+        preview: vec![],
+    };
+    update_preview(app, &mut browser);
+    Ok(browser)
+}
+
+fn update_preview(app: &App, browser: &mut FileBrowser) {
+    browser.preview = match browser.entries.get(browser.item) {
+        Some(entry) if !entry.is_dir => match read_data_file(&entry.path.to_string_lossy()) {
+            Ok(JsonDataFile::Scales(file)) => preview_scales(&file),
+            Ok(JsonDataFile::Sequences(file)) => {
+                let scales = app.sequencer.lock().unwrap().scales.clone();
+                preview_sequences(&file, &scales)
+            }
+            Ok(JsonDataFile::Project(file)) => preview_project(&file),
+            Err(err) => vec![
+                "Not a scale, sequence or project file".to_string(),
+                err.to_string(),
+            ],
+        },
+        _ => vec![],
+    };
+}
+
+fn preview_scales(file: &JsonScaleFile) -> Vec<String> {
+    let mut lines = vec![format!("{} scales", file.scales.len()), String::new()];
+    for scale in &file.scales {
+        lines.push(format!("{}  ({} steps)", scale.name, scale.steps.len()));
+    }
+    lines
+}
+
+fn preview_sequences(file: &JsonSequenceFile, scales: &[Arc<Scale>]) -> Vec<String> {
+    let mut lines = vec![format!("{} sequences", file.sequences.len()), String::new()];
+    for sequence in &file.sequences {
+        let loaded = scales.iter().any(|scale| scale.name == sequence.scale);
+        lines.push(format!(
+            "{}  ({}{}, {} notes)",
+            sequence.name,
+            sequence.scale,
+            if loaded { "" } else { ", not loaded" },
+            sequence.notes.len()
+        ));
+    }
+    lines
+}
+
+fn preview_project(file: &JsonProjectFile) -> Vec<String> {
+    let clock = match file.tempo.sync {
+        JsonSyncMode::External => "external clock".to_string(),
+        JsonSyncMode::Internal => format!("internal clock, {:.0} BPM", file.tempo.bpm),
+    };
+    let mut lines = vec![
+        format!("Project (version {})", file.version),
+        format!("{} -> {}, {}", file.midi.input_port, file.midi.output_port, clock),
+        String::new(),
+        format!("{} scales", file.scales.len()),
+    ];
+    for scale in &file.scales {
+        lines.push(format!("{}  ({} steps)", scale.name, scale.steps.len()));
+    }
+    lines.push(String::new());
+    lines.push(format!("{} sequences", file.sequences.len()));
+    for sequence in &file.sequences {
+        lines.push(format!(
+            "{}  ({}, {} notes)",
+            sequence.name,
+            sequence.scale,
+            sequence.notes.len()
+        ));
+    }
+    lines
+}
+
+/// Loads the file at `path` into the running sequencer, unless its validation finds errors.
+/// Its warnings are shown once it is loaded.
+///
+/// The sequences of a sequence file replace the playlist, or are added after it when
+/// `append` is set. The scales of a scale file are added to the loaded ones, replacing the
+/// scales of the same name. A project replaces everything, settings included, or only adds
+/// its scales and sequences when `append` is set.
+fn load(app: &mut App, path: &Path, append: bool) -> Result<(), io::Error> {
+    let path = path.to_string_lossy().to_string();
+    let mut sequencer = app.sequencer.lock().unwrap();
+    let warnings = match read_data_file(&path)? {
+        JsonDataFile::Scales(file) => {
+            let scales: Vec<Arc<Scale>> = build_scales_from_json_scale_file(file)
+                .into_iter()
+                .map(Arc::new)
+                .collect();
+            let warnings = check(&path, validate_scales(&scales))?;
+            app.history.record(EditCommand::all(&sequencer));
+            for scale in scales {
+                let index = sequencer.scales.iter().position(|s| s.name == scale.name);
+                sequencer.replace_scale(index, (*scale).clone());
+            }
+            warnings
+        }
+        JsonDataFile::Sequences(file) => {
+            let sequences =
+                build_sequences_from_json_sequence_file(&path, file, &sequencer.scales)?;
+            let warnings = check(&path, validate_sequences(&sequences))?;
+            app.history.record(EditCommand::all(&sequencer));
+            if append {
+                for sequence in sequences {
+                    let index = sequencer.sequences.len();
+                    sequencer.insert_sequence(index, sequence);
+                }
+            } else {
+                sequencer.replace_sequences(sequences);
+                sequencer.queue_sequence(0);
+                app.sequence_path = path.clone();
+            }
+            warnings
+        }
+        JsonDataFile::Project(file) => {
+            let (project, warnings) = build_sequencer_from_json_project_file(&path, file)?;
+            app.history.record(EditCommand::all(&sequencer));
+            if append {
+                for scale in &project.scales {
+                    let index = sequencer.scales.iter().position(|s| s.name == scale.name);
+                    sequencer.replace_scale(index, (**scale).clone());
+                }
+                for sequence in project.sequences {
+                    let index = sequencer.sequences.len();
+                    sequencer.insert_sequence(index, sequence);
+                }
+            } else {
+                sequencer.load_project(project);
+                app.project_path = Some(path.clone());
+            }
+            warnings
+        }
+    };
+    drop(sequencer);
+    app.retune_errors = None;
+    app.selection_anchor = None;
+    close(app);
+    match warnings.is_empty() {
+        true => Ok(()),
+        false => Err(io::Error::other(format!(
+            "{} loaded with warnings:\n{}",
+            path, warnings
+        ))),
+    }
+}
@@ -3,12 +3,12 @@ use crossterm::event::KeyEvent;
 use ratatui::Frame;
 use crate::tui::entities::{App};
 
-pub fn draw(frame: &mut Frame) {}
+pub fn draw(_frame: &mut Frame) {}
 
-pub fn handle_key(app: &mut App, key: KeyEvent) -> Result<(), io::Error> {
+pub fn handle_key(_app: &mut App, _key: KeyEvent) -> Result<(), io::Error> {
     Ok(())
 }
 
-pub fn move_to(app: &mut App) -> Result<(), io::Error> {
+pub fn move_to(_app: &mut App) -> Result<(), io::Error> {
     Ok(())
 }
\ No newline at end of file
@@ -49,8 +49,8 @@ pub fn handle_key(app: &mut App, key_event: KeyEvent) -> Result<(), io::Error> {
     if key_event.kind == KeyEventKind::Press {
         return match key_event.code {
             KeyCode::Enter => on_enter(app),
-            KeyCode::Up => Ok(on_up(app)),
-            KeyCode::Down => Ok(on_down(app)),
+            KeyCode::Up => on_up(app),
+            KeyCode::Down => on_down(app),
             _ => {Ok(())}
         }
     }
@@ -63,32 +63,31 @@ fn on_enter(app: &mut App) -> Result<(), io::Error> {
             MainMenuItem::StartSequencer => sequencer_menu::move_to(app),
             MainMenuItem::LinkController => link_controller_menu::move_to(app),
             MainMenuItem::Settings => settings_menu::move_to(app),
-            MainMenuItem::Exit => Ok(exit(app)),
+            MainMenuItem::Exit => {
+                exit(app);
+                Ok(())
+            }
         },
         _ => {Ok(())}
     }
 }
-fn on_up(app: &mut App) {
-    match &app.current_menu {
-        Menu::Main { selected_menu } => {
-            app.current_menu = Menu::Main {
-                selected_menu: MainMenuItem::from_index(
-                    (selected_menu.as_index() + MainMenuItem::length() - 1) % MainMenuItem::length(),
-                ),
-            };
-        }
-        _ => {}
+fn on_up(app: &mut App) -> Result<(), io::Error> {
+    if let Menu::Main { selected_menu } = &app.current_menu {
+        app.current_menu = Menu::Main {
+            selected_menu: MainMenuItem::from_index(
+                (selected_menu.as_index() + MainMenuItem::length() - 1) % MainMenuItem::length(),
+            ),
+        };
     }
+    Ok(())
 }
-fn on_down(app: &mut App) {
-    match &app.current_menu {
-        Menu::Main { selected_menu } => {
-            app.current_menu = Menu::Main {
-                selected_menu: MainMenuItem::from_index(
-                    (selected_menu.as_index() + 1) % MainMenuItem::length(),
-                ),
-            };
-        }
-        _ => {}
+fn on_down(app: &mut App) -> Result<(), io::Error> {
+    if let Menu::Main { selected_menu } = &app.current_menu {
+        app.current_menu = Menu::Main {
+            selected_menu: MainMenuItem::from_index(
+                (selected_menu.as_index() + 1) % MainMenuItem::length(),
+            ),
+        };
     }
+    Ok(())
 }
\ No newline at end of file
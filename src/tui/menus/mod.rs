@@ -1,5 +1,10 @@
 pub mod main_menu;
 pub mod sequencer_menu;
+pub mod note_inspector;
 pub mod link_controller_menu;
 pub mod error_screen;
 pub mod settings_menu;
+pub mod scale_browser;
+pub mod scale_editor;
+pub mod save_as;
+pub mod file_browser;
@@ -0,0 +1,364 @@
+use crate::history::EditCommand;
+use crate::modulation::{PitchEnvelope, Vibrato};
+use crate::note::{ControlChange, Gate, Glide, GlideCurve, Note};
+use crate::tui::entities::{App, Menu, SequencerMenuSelectedItem};
+use crossterm::event::{KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
+use ratatui::layout::Rect;
+use ratatui::prelude::{Color, Style};
+use ratatui::text::ToSpan;
+use ratatui::widgets::{Block, Clear, List, ListItem, ListState};
+use ratatui::Frame;
+use std::io;
+
+/// The lines of the inspector, in display order.
+enum InspectorField {
+    Velocity,
+    Panning,
+    Duration,
+    Gate,
+    Microtiming,
+    Glide,
+    Vibrato,
+    PitchEnvelope,
+    Program,
+    Aftertouch,
+    ControlChange { index: usize },
+    AddControlChange,
+}
+
+fn fields(note: &Note) -> Vec<InspectorField> {
+    let mut fields = vec![
+        InspectorField::Velocity,
+        InspectorField::Panning,
+        InspectorField::Duration,
+        InspectorField::Gate,
+        InspectorField::Microtiming,
+        InspectorField::Glide,
+        InspectorField::Vibrato,
+        InspectorField::PitchEnvelope,
+        InspectorField::Program,
+        InspectorField::Aftertouch,
+    ];
+    for index in 0..note.control_changes.len() {
+        fields.push(InspectorField::ControlChange { index });
+    }
+    fields.push(InspectorField::AddControlChange);
+    fields
+}
+
+fn describe(note: &Note, field: &InspectorField) -> String {
+    let optional = |value: Option<u8>| match value {
+        None => "-".to_string(),
+        Some(v) => v.to_string(),
+    };
+    match field {
+        InspectorField::Velocity => format!("Velocity: {}", note.velocity),
+        InspectorField::Panning => format!("Panning: {}", optional(note.panning)),
+        InspectorField::Duration => format!("Duration: {:.3} beats", note.duration.duration),
+        InspectorField::Gate => match note.gate {
+            Gate::Percent(percent) => format!("Gate: {}%", percent),
+            Gate::Beats(beats) => format!("Gate: {:.3} beats", beats),
+        },
+        InspectorField::Microtiming => format!("Microtiming: {:+.2} tick", note.microtiming),
+        InspectorField::Glide => match note.glide {
+            None => "Glide: -".to_string(),
+            Some(glide) => format!("Glide: {:.3} beats, {}", glide.time, glide.curve),
+        },
+        InspectorField::Vibrato => match note.modulation.vibrato {
+            None => "Vibrato: -".to_string(),
+            Some(vibrato) => format!(
+                "Vibrato: ±{} cents, {} Hz",
+                vibrato.depth_cents, vibrato.rate_hz
+            ),
+        },
+        InspectorField::PitchEnvelope => match note.modulation.envelope {
+            None => "Pitch envelope: -".to_string(),
+            Some(envelope) => format!(
+                "Pitch envelope: {:+} cents, {} ms",
+                envelope.start_cents, envelope.time_ms
+            ),
+        },
+        InspectorField::Program => format!("Program change: {}", optional(note.program)),
+        InspectorField::Aftertouch => format!("Aftertouch: {}", optional(note.aftertouch)),
+        InspectorField::ControlChange { index } => {
+            let cc = note.control_changes[*index];
+            format!("CC {}: {}", cc.controller, cc.value)
+        }
+        InspectorField::AddControlChange => "+ Add CC".to_string(),
+    }
+}
+
+fn get_selected(app: &App) -> Option<(usize, usize)> {
+    match &app.current_menu {
+        Menu::Sequencer {
+            selected_menu: SequencerMenuSelectedItem::Inspector { item, field },
+        } => Some((*item, *field)),
+        _ => None,
+    }
+}
+
+fn selected_note(app: &App, item: usize) -> Option<Note> {
+    let sequencer = app.sequencer.lock().unwrap();
+    sequencer.sequences[sequencer.current_sequence_index]
+        .notes
+        .get(item)
+        .cloned()
+}
+
+pub fn draw(frame: &mut Frame, app: &App, area: Rect) {
+    let Some((item, field)) = get_selected(app) else {
+        return;
+    };
+    let Some(note) = selected_note(app, item) else {
+        return;
+    };
+
+    let items: Vec<ListItem> = fields(&note)
+        .iter()
+        .map(|f| ListItem::new(describe(&note, f)))
+        .collect();
+
+    let width = 44.min(area.width);
+    let height = (items.len() as u16 + 2).min(area.height);
+    let popup = Rect::new(
+        area.left() + (area.width - width) / 2,
+        area.top() + (area.height - height) / 2,
+        width,
+        height,
+    );
+
+    let title = format!(" {} ", note.get_common_name());
+    let list = List::new(items)
+        .block(
+            Block::bordered()
+                .style(Style::default().fg(Color::White))
+                .title(title.to_span().into_centered_line())
+                .title_bottom(" ←/→ change  [/] CC, rate, time  Del remove  Esc close ".to_span()),
+        )
+        .style(Style::default().fg(Color::LightBlue))
+        .highlight_style(Style::default().fg(Color::Black).bg(Color::LightBlue));
+
+    let mut state = ListState::default();
+    state.select(Some(field));
+
+    frame.render_widget(Clear, popup);
+    frame.render_stateful_widget(list, popup, &mut state);
+}
+
+pub fn handle_key(app: &mut App, key_event: KeyEvent) -> Result<(), io::Error> {
+    if key_event.kind != KeyEventKind::Press {
+        return Ok(());
+    }
+    let (item, field) = get_selected(app).ok_or(io::Error::other("Out of bounds"))?;
+    let step = if key_event.modifiers.contains(KeyModifiers::SHIFT) {
+        10
+    } else {
+        1
+    };
+    match key_event.code {
+        KeyCode::Esc => {
+            app.current_menu = Menu::Sequencer {
+                selected_menu: SequencerMenuSelectedItem::Note { item },
+            };
+            Ok(())
+        }
+        KeyCode::Up => select_field(app, item, field.saturating_sub(1)),
+        KeyCode::Down => select_field(app, item, field + 1),
+        KeyCode::Left => edit_field(app, item, field, |note, f| change(note, f, -step)),
+        KeyCode::Right => edit_field(app, item, field, |note, f| change(note, f, step)),
+        KeyCode::Char('[') => {
+            edit_field(app, item, field, |note, f| change_secondary(note, f, -step))
+        }
+        KeyCode::Char(']') => {
+            edit_field(app, item, field, |note, f| change_secondary(note, f, step))
+        }
+        KeyCode::Enter => edit_field(app, item, field, toggle),
+        KeyCode::Delete | KeyCode::Backspace => {
+            edit_field(app, item, field, remove)?;
+            select_field(app, item, field)
+        }
+        _ => Ok(()),
+    }
+}
+
+pub fn open(app: &mut App, item: usize) -> Result<(), io::Error> {
+    app.current_menu = Menu::Sequencer {
+        selected_menu: SequencerMenuSelectedItem::Inspector { item, field: 0 },
+    };
+    Ok(())
+}
+
+fn select_field(app: &mut App, item: usize, field: usize) -> Result<(), io::Error> {
+    let note = selected_note(app, item).ok_or(io::Error::other("Out of bounds"))?;
+    app.current_menu = Menu::Sequencer {
+        selected_menu: SequencerMenuSelectedItem::Inspector {
+            item,
+            field: field.min(fields(&note).len() - 1),
+        },
+    };
+    Ok(())
+}
+
+/// Applies `edit` to the selected field of the note. Notes can't be edited while playing.
+/// Only edits changing the note can be undone.
+fn edit_field(
+    app: &mut App,
+    item: usize,
+    field: usize,
+    edit: impl FnOnce(&mut Note, &InspectorField),
+) -> Result<(), io::Error> {
+    let mut sequencer = app.sequencer.lock().unwrap();
+    if sequencer.started {
+        return Ok(());
+    }
+    let current_sequence_i = sequencer.current_sequence_index;
+    let command = EditCommand::sequence(&sequencer, current_sequence_i);
+    let note = sequencer.sequences[current_sequence_i]
+        .notes
+        .get_mut(item)
+        .ok_or(io::Error::other("Out of bounds"))?;
+    let before = note.clone();
+    let fields = fields(note);
+    if let Some(f) = fields.get(field) {
+        edit(note, f);
+    }
+    if *note != before {
+        app.history.record(command);
+    }
+    Ok(())
+}
+
+fn change_optional(value: Option<u8>, step: i32) -> Option<u8> {
+    match value {
+        None if step > 0 => Some(0),
+        None => None,
+        Some(v) if v as i32 + step < 0 => None,
+        Some(v) => Some((v as i32 + step).min(127) as u8),
+    }
+}
+
+fn change(note: &mut Note, field: &InspectorField, step: i32) {
+    match field {
+        InspectorField::Velocity => {
+            note.velocity = (note.velocity as i32 + step).clamp(1, 127) as u8;
+        }
+        InspectorField::Duration => {
+            let ticks = (note.duration.get_tick_length() as i32 + step).clamp(1, 255);
+            note.duration.duration = ticks as f64 / 24f64;
+        }
+        InspectorField::Gate => {
+            note.gate = match note.gate {
+                Gate::Percent(percent) => Gate::Percent((percent + 5f64 * step as f64).max(5f64)),
+                Gate::Beats(beats) => {
+                    Gate::Beats(((beats * 24f64).round() + step as f64).max(1f64) / 24f64)
+                }
+            };
+        }
+        InspectorField::Microtiming => {
+            note.microtiming = (note.microtiming + 0.05 * step as f64).clamp(-0.95, 0.95);
+        }
+        InspectorField::Glide => {
+            let ticks = note
+                .glide
+                .map_or(0f64, |glide| (glide.time * 24f64).round())
+                + step as f64;
+            note.glide = match note.glide {
+                _ if ticks <= 0f64 => None,
+                None => Some(Glide {
+                    time: ticks / 24f64,
+                    curve: GlideCurve::Linear,
+                }),
+                Some(glide) => Some(Glide {
+                    time: ticks / 24f64,
+                    ..glide
+                }),
+            };
+        }
+        InspectorField::Vibrato => {
+            let vibrato = note.modulation.vibrato.get_or_insert(Vibrato {
+                depth_cents: 0f64,
+                rate_hz: 5f64,
+                delay_ms: 0f64,
+            });
+            vibrato.depth_cents = (vibrato.depth_cents + step as f64).clamp(0f64, 200f64);
+        }
+        InspectorField::PitchEnvelope => {
+            let envelope = note.modulation.envelope.get_or_insert(PitchEnvelope {
+                start_cents: 0f64,
+                time_ms: 100f64,
+            });
+            envelope.start_cents = (envelope.start_cents + step as f64).clamp(-200f64, 200f64);
+        }
+        InspectorField::Panning => note.panning = change_optional(note.panning, step),
+        InspectorField::Program => note.program = change_optional(note.program, step),
+        InspectorField::Aftertouch => note.aftertouch = change_optional(note.aftertouch, step),
+        InspectorField::ControlChange { index } => {
+            let cc = &mut note.control_changes[*index];
+            cc.value = (cc.value as i32 + step).clamp(0, 127) as u8;
+        }
+        InspectorField::AddControlChange => {}
+    }
+}
+
+/// Changes the second value of a line: the controller of a CC, the rate of the vibrato or the
+/// time of the pitch envelope.
+fn change_secondary(note: &mut Note, field: &InspectorField, step: i32) {
+    match field {
+        InspectorField::ControlChange { index } => {
+            let cc = &mut note.control_changes[*index];
+            // Controllers 120 to 127 are channel mode messages, not parameters
+            cc.controller = (cc.controller as i32 + step).clamp(0, 119) as u8;
+        }
+        InspectorField::Vibrato => {
+            if let Some(vibrato) = &mut note.modulation.vibrato {
+                vibrato.rate_hz = (vibrato.rate_hz + 0.5 * step as f64).clamp(0.5, 20f64);
+            }
+        }
+        InspectorField::PitchEnvelope => {
+            if let Some(envelope) = &mut note.modulation.envelope {
+                envelope.time_ms = (envelope.time_ms + 10f64 * step as f64).clamp(10f64, 2000f64);
+            }
+        }
+        _ => {}
+    }
+}
+
+/// Switches the gate between percentage and beats, keeping its length, changes the curve of
+/// the glide, or adds a CC.
+fn toggle(note: &mut Note, field: &InspectorField) {
+    match field {
+        InspectorField::Gate => {
+            note.gate = match note.gate {
+                Gate::Percent(_) => Gate::Beats(note.get_gate_ticks() / 24f64),
+                Gate::Beats(beats) => {
+                    Gate::Percent((beats / note.duration.duration * 100f64).round())
+                }
+            };
+        }
+        InspectorField::Glide => {
+            if let Some(glide) = &mut note.glide {
+                glide.curve = glide.curve.next();
+            }
+        }
+        InspectorField::AddControlChange => note.control_changes.push(ControlChange {
+            controller: 1,
+            value: 0,
+        }),
+        _ => {}
+    }
+}
+
+fn remove(note: &mut Note, field: &InspectorField) {
+    match field {
+        InspectorField::Glide => note.glide = None,
+        InspectorField::Vibrato => note.modulation.vibrato = None,
+        InspectorField::PitchEnvelope => note.modulation.envelope = None,
+        InspectorField::Panning => note.panning = None,
+        InspectorField::Program => note.program = None,
+        InspectorField::Aftertouch => note.aftertouch = None,
+        InspectorField::ControlChange { index } => {
+            note.control_changes.remove(*index);
+        }
+        _ => {}
+    }
+}
@@ -0,0 +1,120 @@
+use crate::tui::entities::{App, Menu, SequencerMenuItem, SequencerMenuSelectedItem};
+use crate::tui::menus::sequencer_menu;
+use crossterm::event::{KeyCode, KeyEvent, KeyEventKind};
+use ratatui::layout::Rect;
+use ratatui::prelude::{Color, Line, Style};
+use ratatui::widgets::{Block, Clear, Paragraph};
+use ratatui::Frame;
+use std::io;
+use std::path::Path;
+
+fn get_selected(app: &App) -> Option<(String, bool)> {
+    match &app.current_menu {
+        Menu::Sequencer {
+            selected_menu: SequencerMenuSelectedItem::SaveAs { path, project },
+        } => Some((path.clone(), *project)),
+        _ => None,
+    }
+}
+
+fn set_selected(app: &mut App, path: String, project: bool) {
+    app.current_menu = Menu::Sequencer {
+        selected_menu: SequencerMenuSelectedItem::SaveAs { path, project },
+    };
+}
+
+pub fn draw(frame: &mut Frame, app: &App, area: Rect) {
+    let Some((path, project)) = get_selected(app) else {
+        return;
+    };
+
+    let width = 64.min(area.width);
+    let height = 3.min(area.height);
+    let popup = Rect::new(
+        area.left() + (area.width - width) / 2,
+        area.top() + (area.height - height) / 2,
+        width,
+        height,
+    );
+
+    let title = match project {
+        true => " Save project as ",
+        false => " Save sequences as ",
+    };
+    let paragraph = Paragraph::new(format!("{}_", path))
+        .block(
+            Block::bordered()
+                .style(Style::default().fg(Color::White))
+                .title(Line::from(title).centered())
+                .title_bottom(Line::from(" Enter save  Esc cancel ")),
+        )
+        .style(Style::default().fg(Color::LightBlue));
+
+    frame.render_widget(Clear, popup);
+    frame.render_widget(paragraph, popup);
+}
+
+pub fn handle_key(app: &mut App, key_event: KeyEvent) -> Result<(), io::Error> {
+    if key_event.kind != KeyEventKind::Press {
+        return Ok(());
+    }
+    let (mut path, project) = get_selected(app).ok_or(io::Error::other("Out of bounds"))?;
+    match key_event.code {
+        KeyCode::Enter => return save(app, path, project),
+        KeyCode::Esc => close(app, project),
+        KeyCode::Backspace => {
+            path.pop();
+            set_selected(app, path, project);
+        }
+        KeyCode::Char(c) => {
+            path.push(c);
+            set_selected(app, path, project);
+        }
+        _ => {}
+    }
+    Ok(())
+}
+
+/// Opens the dialog with the path the sequences, or the project, were last loaded from or
+/// saved to. A project is suggested next to the sequence file when there is none yet.
+pub fn open(app: &mut App, project: bool) -> Result<(), io::Error> {
+    let path = match (project, &app.project_path) {
+        (true, Some(path)) => path.clone(),
+        (true, None) => Path::new(&app.sequence_path)
+            .with_file_name("project.json")
+            .to_string_lossy()
+            .to_string(),
+        (false, _) => app.sequence_path.clone(),
+    };
+    set_selected(app, path, project);
+    Ok(())
+}
+
+fn close(app: &mut App, project: bool) {
+    let item = match project {
+        true => SequencerMenuItem::SaveProjectAs,
+        false => SequencerMenuItem::SaveAs,
+    };
+    app.current_menu = Menu::Sequencer {
+        selected_menu: SequencerMenuSelectedItem::SubMenuItem { item },
+    };
+}
+
+/// Saves to the typed path, which "Save" then writes to. Sequences saved from a project are
+/// a copy: "Save" keeps writing the project. The dialog stays open if the file can't be
+/// written, to fix the path.
+fn save(app: &mut App, path: String, project: bool) -> Result<(), io::Error> {
+    let path = path.trim().to_string();
+    if path.is_empty() {
+        return Err(io::Error::other("No file name given"));
+    }
+    if project {
+        sequencer_menu::save_project(app, &path)?;
+        app.project_path = Some(path);
+    } else {
+        sequencer_menu::save(app, &path)?;
+        app.sequence_path = path;
+    }
+    close(app, project);
+    Ok(())
+}
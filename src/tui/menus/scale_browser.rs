@@ -0,0 +1,210 @@
+use crate::clock::DEFAULT_TICK_PERIOD;
+use crate::history::EditCommand;
+use crate::note::{Note, NoteDuration};
+use crate::scheduler::ScheduledEvent;
+use crate::tui::entities::{App, Menu, MenuItemList, SequencerMenuItem, SequencerMenuSelectedItem};
+use crate::tui::menus::scale_editor;
+use crossterm::event::{KeyCode, KeyEvent, KeyEventKind};
+use ratatui::layout::Rect;
+use ratatui::prelude::{Color, Line, Style, Text};
+use ratatui::text::ToSpan;
+use ratatui::widgets::{Block, Clear, List, ListItem, ListState};
+use ratatui::Frame;
+use std::io;
+use std::sync::atomic::Ordering;
+use std::time::Instant;
+
+/// Duration of each step of the preview, in beats.
+const PREVIEW_STEP: f64 = 0.25;
+
+fn get_selected(app: &App) -> Option<usize> {
+    match &app.current_menu {
+        Menu::Sequencer {
+            selected_menu: SequencerMenuSelectedItem::Scale { item },
+        } => Some(*item),
+        _ => None,
+    }
+}
+
+pub fn draw(frame: &mut Frame, app: &App, area: Rect) {
+    let Some(item) = get_selected(app) else {
+        return;
+    };
+    let sequencer = app.sequencer.lock().unwrap();
+    let current_scale_name = sequencer.current_scale_name();
+
+    let items: Vec<ListItem> = sequencer
+        .scales
+        .iter()
+        .map(|scale| {
+            let marker = if scale.name == current_scale_name {
+                "* "
+            } else {
+                "  "
+            };
+            let cents = scale
+                .steps
+                .iter()
+                .map(|step| format!("{:.0}", step * 100f64))
+                .collect::<Vec<_>>()
+                .join(" ");
+            ListItem::new(Text::from(vec![
+                Line::from(format!(
+                    "{}{} ({} steps)",
+                    marker,
+                    scale.name,
+                    scale.steps.len()
+                )),
+                Line::from(format!("    {}", cents)),
+            ]))
+        })
+        .collect();
+    drop(sequencer);
+
+    let width = 64.min(area.width);
+    let height = (items.len() as u16 * 2 + 2).min(area.height);
+    let popup = Rect::new(
+        area.left() + (area.width - width) / 2,
+        area.top() + (area.height - height) / 2,
+        width,
+        height,
+    );
+
+    let bottom = format!(
+        " Enter switch  ←/→ retune: {}  p preview  e edit  n new  Esc close ",
+        app.retune_mode
+    );
+    let list = List::new(items)
+        .block(
+            Block::bordered()
+                .style(Style::default().fg(Color::White))
+                .title(" Scales ".to_span().into_centered_line())
+                .title_bottom(Line::from(bottom)),
+        )
+        .style(Style::default().fg(Color::LightBlue))
+        .highlight_style(Style::default().fg(Color::Black).bg(Color::LightBlue));
+
+    let mut state = ListState::default();
+    state.select(Some(item));
+
+    frame.render_widget(Clear, popup);
+    frame.render_stateful_widget(list, popup, &mut state);
+}
+
+pub fn handle_key(app: &mut App, key_event: KeyEvent) -> Result<(), io::Error> {
+    if key_event.kind != KeyEventKind::Press {
+        return Ok(());
+    }
+    let item = get_selected(app).ok_or(io::Error::other("Out of bounds"))?;
+    match key_event.code {
+        KeyCode::Esc => {
+            close(app);
+            Ok(())
+        }
+        KeyCode::Up => select(app, item.saturating_sub(1)),
+        KeyCode::Down => select(app, item + 1),
+        KeyCode::Left | KeyCode::Right => {
+            app.retune_mode = app.retune_mode.next();
+            Ok(())
+        }
+        KeyCode::Char('p') => preview(app, item),
+        KeyCode::Enter => switch_scale(app, item),
+        KeyCode::Char('e') => scale_editor::edit(app, item),
+        KeyCode::Char('n') => scale_editor::create(app, item),
+        _ => Ok(()),
+    }
+}
+
+/// Opens the browser on the scale of the current sequence.
+pub fn open(app: &mut App) -> Result<(), io::Error> {
+    let sequencer = app.sequencer.lock().unwrap();
+    let current_scale_name = sequencer.current_scale_name();
+    let item = sequencer
+        .scales
+        .iter()
+        .position(|s| s.name == current_scale_name)
+        .unwrap_or(0);
+    drop(sequencer);
+    select(app, item)
+}
+
+fn close(app: &mut App) {
+    app.current_menu = Menu::Sequencer {
+        selected_menu: SequencerMenuSelectedItem::SubMenuItem {
+            item: SequencerMenuItem::from_index(SequencerMenuItem::Scale.as_index()),
+        },
+    };
+}
+
+pub fn select(app: &mut App, item: usize) -> Result<(), io::Error> {
+    let scales_len = app.sequencer.lock().unwrap().scales.len();
+    if scales_len == 0 {
+        return Err(io::Error::other("No scale loaded"));
+    }
+    app.current_menu = Menu::Sequencer {
+        selected_menu: SequencerMenuSelectedItem::Scale {
+            item: item.min(scales_len - 1),
+        },
+    };
+    Ok(())
+}
+
+/// Plays every step of the scale, then the octave, from the octave of the current note.
+fn preview(app: &mut App, item: usize) -> Result<(), io::Error> {
+    let sequencer = app.sequencer.lock().unwrap();
+    let scale = sequencer.scales[item].clone();
+    let octave = sequencer.current_note().octave;
+    drop(sequencer);
+
+    let degrees = (0..scale.steps.len())
+        .map(|i| (i, octave))
+        .chain(std::iter::once((0, octave.saturating_add(1))));
+    let notes = degrees
+        .map(|(note_index, octave)| {
+            Note::new(scale.clone(), note_index, octave, preview_duration(), 100)
+        })
+        .collect();
+    play_preview(app, notes)
+}
+
+pub(crate) fn preview_duration() -> NoteDuration {
+    NoteDuration {
+        duration: PREVIEW_STEP,
+    }
+}
+
+/// Plays the notes one after the other, at 120 BPM.
+///
+/// The notes go through the scheduler, which is only sent by the main loop while the
+/// sequencer is on.
+pub(crate) fn play_preview(app: &mut App, notes: Vec<Note>) -> Result<(), io::Error> {
+    if !app.sequencer_on.load(Ordering::SeqCst) {
+        return Err(io::Error::other("Turn the sequencer on to preview a scale"));
+    }
+    let tick_period = DEFAULT_TICK_PERIOD;
+    let now = Instant::now();
+    let mut sequencer = app.sequencer.lock().unwrap();
+    let mut at = now;
+    for note in notes {
+        let length = tick_period.mul_f64(note.duration.duration * 24f64);
+        sequencer
+            .scheduler
+            .schedule(at, ScheduledEvent::NoteOn { note, tick_period });
+        at += length;
+    }
+    Ok(())
+}
+
+/// Moves the current sequence into the selected scale, with the retune mode of the browser.
+fn switch_scale(app: &mut App, item: usize) -> Result<(), io::Error> {
+    let mut sequencer = app.sequencer.lock().unwrap();
+    let scale = sequencer.scales[item].clone();
+    let current_sequence_i = sequencer.current_sequence_index;
+    app.history
+        .record(EditCommand::sequence(&sequencer, current_sequence_i));
+    let errors = sequencer.sequences[current_sequence_i].retune(scale, app.retune_mode);
+    drop(sequencer);
+    app.retune_errors = Some((current_sequence_i, errors));
+    close(app);
+    Ok(())
+}
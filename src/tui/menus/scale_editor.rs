@@ -0,0 +1,397 @@
+use crate::history::EditCommand;
+use crate::json::{
+    save_project_to_json_file, save_scales_to_json_file, save_sequences_to_json_file,
+};
+use crate::note::{Note, Scale};
+use crate::sequencer::Sequencer;
+use crate::tui::file_watcher;
+use crate::tui::entities::{App, Menu, SequencerMenuSelectedItem};
+use crate::tui::menus::scale_browser;
+use crossterm::event::{KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
+use ratatui::layout::Rect;
+use ratatui::prelude::{Color, Line, Style};
+use ratatui::widgets::{Block, Clear, List, ListItem, ListState};
+use ratatui::Frame;
+use std::io;
+use std::sync::Arc;
+
+/// What the text typed in the editor is written to.
+#[derive(Clone, Copy)]
+enum InputField {
+    Cents,
+    /// A frequency ratio to the first degree, like `3/2` or `1.5`.
+    Ratio,
+    DegreeName,
+    ScaleName,
+}
+
+impl InputField {
+    fn label(&self) -> &str {
+        match self {
+            InputField::Cents => "Cents",
+            InputField::Ratio => "Ratio",
+            InputField::DegreeName => "Degree name",
+            InputField::ScaleName => "Scale name",
+        }
+    }
+}
+
+/// A copy of a scale being edited. It replaces the loaded scale only when saved, since the
+/// notes of the sequences share the loaded one.
+///
+/// `item` is the index of the scale in the loaded scales, `None` for a new scale.
+#[derive(Clone)]
+pub struct ScaleEditor {
+    item: Option<usize>,
+    scale: Scale,
+    step: usize,
+    input: Option<(InputField, String)>,
+}
+
+fn get_selected(app: &App) -> Option<ScaleEditor> {
+    match &app.current_menu {
+        Menu::Sequencer {
+            selected_menu: SequencerMenuSelectedItem::ScaleEditor { editor },
+        } => Some(editor.clone()),
+        _ => None,
+    }
+}
+
+fn set_selected(app: &mut App, editor: ScaleEditor) {
+    app.current_menu = Menu::Sequencer {
+        selected_menu: SequencerMenuSelectedItem::ScaleEditor { editor },
+    };
+}
+
+pub fn draw(frame: &mut Frame, app: &App, area: Rect) {
+    let Some(editor) = get_selected(app) else {
+        return;
+    };
+    let scale = &editor.scale;
+
+    let items: Vec<ListItem> = scale
+        .steps
+        .iter()
+        .zip(&scale.note_names)
+        .enumerate()
+        .map(|(i, (step, name))| {
+            ListItem::new(format!(
+                "{:>3}  {:<12} {:>8.1}c  {:>7.4}",
+                i,
+                name,
+                step * 100f64,
+                2f64.powf(step / 12f64)
+            ))
+        })
+        .collect();
+
+    let width = 64.min(area.width);
+    let height = (items.len() as u16 + 2).min(area.height);
+    let popup = Rect::new(
+        area.left() + (area.width - width) / 2,
+        area.top() + (area.height - height) / 2,
+        width,
+        height,
+    );
+
+    let bottom = match &editor.input {
+        Some((field, text)) => format!(" {}: {}_  Enter ok  Esc cancel ", field.label(), text),
+        None => " c/r cents/ratio  n/N rename  a add  Del  ⇧↑/↓ move  p play  s save ".to_string(),
+    };
+    let list = List::new(items)
+        .block(
+            Block::bordered()
+                .style(Style::default().fg(Color::White))
+                .title(Line::from(format!(" Edit {} ", scale.name)).centered())
+                .title_bottom(Line::from(bottom)),
+        )
+        .style(Style::default().fg(Color::LightBlue))
+        .highlight_style(Style::default().fg(Color::Black).bg(Color::LightBlue));
+
+    let mut state = ListState::default();
+    state.select(Some(editor.step));
+
+    frame.render_widget(Clear, popup);
+    frame.render_stateful_widget(list, popup, &mut state);
+}
+
+pub fn handle_key(app: &mut App, key_event: KeyEvent) -> Result<(), io::Error> {
+    if key_event.kind != KeyEventKind::Press {
+        return Ok(());
+    }
+    let mut editor = get_selected(app).ok_or(io::Error::other("Out of bounds"))?;
+    if let Some((field, mut text)) = editor.input.take() {
+        match key_event.code {
+            KeyCode::Enter => apply_input(&mut editor, field, &text)?,
+            KeyCode::Esc => {}
+            KeyCode::Backspace => {
+                text.pop();
+                editor.input = Some((field, text));
+            }
+            KeyCode::Char(c) => {
+                text.push(c);
+                editor.input = Some((field, text));
+            }
+            _ => editor.input = Some((field, text)),
+        }
+        set_selected(app, editor);
+        return Ok(());
+    }
+
+    let shift = key_event.modifiers.contains(KeyModifiers::SHIFT);
+    let last = editor.scale.steps.len() - 1;
+    match key_event.code {
+        KeyCode::Esc => return close(app, editor.item.unwrap_or(0)),
+        KeyCode::Up if shift && editor.step > 0 => {
+            swap_steps(&mut editor.scale, editor.step, editor.step - 1);
+            editor.step -= 1;
+        }
+        KeyCode::Down if shift && editor.step < last => {
+            swap_steps(&mut editor.scale, editor.step, editor.step + 1);
+            editor.step += 1;
+        }
+        KeyCode::Up => editor.step = editor.step.saturating_sub(1),
+        KeyCode::Down => editor.step = (editor.step + 1).min(last),
+        KeyCode::Char('c') => start_input(&mut editor, InputField::Cents),
+        KeyCode::Char('r') => start_input(&mut editor, InputField::Ratio),
+        KeyCode::Char('n') => start_input(&mut editor, InputField::DegreeName),
+        KeyCode::Char('N') => start_input(&mut editor, InputField::ScaleName),
+        KeyCode::Char('a') => add_step(&mut editor),
+        KeyCode::Delete | KeyCode::Backspace if last > 0 => {
+            editor.scale.steps.remove(editor.step);
+            editor.scale.note_names.remove(editor.step);
+            editor.step = editor.step.min(last - 1);
+        }
+        KeyCode::Char('p') => audition(app, &editor)?,
+        KeyCode::Char('s') => return save(app, editor),
+        _ => {}
+    }
+    set_selected(app, editor);
+    Ok(())
+}
+
+/// Opens the editor on a copy of a loaded scale.
+pub fn edit(app: &mut App, item: usize) -> Result<(), io::Error> {
+    let scale = (*app.sequencer.lock().unwrap().scales[item]).clone();
+    open(app, Some(item), scale);
+    Ok(())
+}
+
+/// Opens the editor on a new scale, starting as a copy of a loaded one.
+pub fn create(app: &mut App, from: usize) -> Result<(), io::Error> {
+    let mut scale = (*app.sequencer.lock().unwrap().scales[from]).clone();
+    scale.name = format!("{} copy", scale.name);
+    open(app, None, scale);
+    Ok(())
+}
+
+fn open(app: &mut App, item: Option<usize>, scale: Scale) {
+    set_selected(
+        app,
+        ScaleEditor {
+            item,
+            scale,
+            step: 0,
+            input: None,
+        },
+    );
+}
+
+fn close(app: &mut App, item: usize) -> Result<(), io::Error> {
+    scale_browser::select(app, item)
+}
+
+fn start_input(editor: &mut ScaleEditor, field: InputField) {
+    editor.input = Some((field, String::new()));
+}
+
+fn apply_input(editor: &mut ScaleEditor, field: InputField, text: &str) -> Result<(), io::Error> {
+    let text = text.trim();
+    match field {
+        InputField::Cents => editor.scale.steps[editor.step] = parse_number(text)? / 100f64,
+        InputField::Ratio => {
+            let ratio = match text.split_once('/') {
+                Some((numerator, denominator)) => {
+                    parse_number(numerator.trim())? / parse_number(denominator.trim())?
+                }
+                None => parse_number(text)?,
+            };
+            if !ratio.is_finite() || ratio <= 0f64 {
+                return Err(io::Error::other(format!("Not a valid ratio: {}", text)));
+            }
+            editor.scale.steps[editor.step] = 12f64 * ratio.log2();
+        }
+        InputField::DegreeName if !text.is_empty() => {
+            editor.scale.note_names[editor.step] = text.to_string()
+        }
+        InputField::ScaleName if !text.is_empty() => editor.scale.name = text.to_string(),
+        _ => {}
+    }
+    Ok(())
+}
+
+fn parse_number(text: &str) -> Result<f64, io::Error> {
+    text.parse::<f64>()
+        .map_err(|_| io::Error::other(format!("Not a number: {}", text)))
+}
+
+fn swap_steps(scale: &mut Scale, a: usize, b: usize) {
+    scale.steps.swap(a, b);
+    scale.note_names.swap(a, b);
+}
+
+/// Adds a step halfway between the selected one and the next, or the octave after the last.
+fn add_step(editor: &mut ScaleEditor) {
+    let steps = &editor.scale.steps;
+    let next = steps.get(editor.step + 1).copied().unwrap_or(12f64);
+    let step = (steps[editor.step] + next) / 2f64;
+    editor.step += 1;
+    editor.scale.steps.insert(editor.step, step);
+    editor
+        .scale
+        .note_names
+        .insert(editor.step, format!("{:.0}c", step * 100f64));
+}
+
+/// Plays the selected step as the scale is now, in the octave of the current note.
+fn audition(app: &mut App, editor: &ScaleEditor) -> Result<(), io::Error> {
+    let octave = app.sequencer.lock().unwrap().current_note().octave;
+    let scale = Arc::new(editor.scale.clone());
+    let note = Note::new(
+        scale,
+        editor.step,
+        octave,
+        scale_browser::preview_duration(),
+        100,
+    );
+    scale_browser::play_preview(app, vec![note])
+}
+
+/// Replaces the loaded scale with the edited one and writes all the scales to the scale file,
+/// or to the project when one is loaded.
+///
+/// Sequences refer to their scale by name: renaming a scale they use also writes the sequence
+/// file, so that they still find it when loaded.
+fn save(app: &mut App, mut editor: ScaleEditor) -> Result<(), io::Error> {
+    let mut sequencer = app.sequencer.lock().unwrap();
+    let name_taken = sequencer
+        .scales
+        .iter()
+        .enumerate()
+        .any(|(i, s)| s.name == editor.scale.name && Some(i) != editor.item);
+    if name_taken {
+        return Err(io::Error::other(format!(
+            "A scale named {} already exists",
+            editor.scale.name
+        )));
+    }
+    app.history.record(EditCommand::all(&sequencer));
+    let renamed = renames_used_scale(&sequencer, editor.item, &editor.scale.name);
+    let item = sequencer.replace_scale(editor.item, editor.scale.clone());
+    let written = match &app.project_path {
+        Some(path) => save_project_to_json_file(path, &sequencer),
+        None => save_scale_files(&app.scale_path, &app.sequence_path, &sequencer, renamed),
+    };
+    drop(sequencer);
+    match written {
+        Ok(()) => {
+            file_watcher::saved(app);
+            close(app, item)
+        }
+        Err(err) => {
+            // The scale is loaded even if the file could not be written: keep editing it
+            editor.item = Some(item);
+            set_selected(app, editor);
+            Err(err)
+        }
+    }
+}
+
+/// Whether saving the scale at `item` as `name` renames a scale that sequences use.
+fn renames_used_scale(sequencer: &Sequencer, item: Option<usize>, name: &str) -> bool {
+    let Some(old_name) = item.map(|i| &sequencer.scales[i].name) else {
+        return false;
+    };
+    old_name != name && sequencer.sequences.iter().any(|s| s.scale().name == *old_name)
+}
+
+fn save_scale_files(
+    scale_path: &str,
+    sequence_path: &str,
+    sequencer: &Sequencer,
+    renamed: bool,
+) -> Result<(), io::Error> {
+    save_scales_to_json_file(scale_path, &sequencer.scales)?;
+    if renamed {
+        save_sequences_to_json_file(sequence_path, &sequencer.sequences)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::json::get_sequencer_from_json;
+    use crate::paths::DataSource;
+    use crate::sequencer::Sequence;
+    use std::fs;
+    use std::path::PathBuf;
+
+    const SCALES: &str = r#"{"scales": [
+        {"name": "5-TET", "steps": [0, 2.4, 4.8, 7.2, 9.6], "note_names": ["a", "b", "c", "d", "e"]}
+    ]}"#;
+    const SEQUENCES: &str = r#"{"sequences": [
+        {"name": "Intro", "scale": "5-TET", "repeat": 1, "notes": [
+            {"note_index": 3, "octave": 5, "duration": 1, "velocity": 100}
+        ]}
+    ]}"#;
+
+    fn temp_path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("microtonal-sequencer-{}-{}", std::process::id(), name))
+    }
+
+    #[test]
+    fn renamed_scales_still_load_with_their_sequences() {
+        let scale_path = temp_path("rename-scales.json");
+        let sequence_path = temp_path("rename-sequences.json");
+        fs::write(&scale_path, SCALES).unwrap();
+        fs::write(&sequence_path, SEQUENCES).unwrap();
+        let load = |scales: &PathBuf, sequences: &PathBuf| {
+            let sources = (DataSource::File(scales.clone()), DataSource::File(sequences.clone()));
+            get_sequencer_from_json(&sources.0, &sources.1).unwrap().0
+        };
+
+        let mut sequencer = load(&scale_path, &sequence_path);
+        let mut scale = (*sequencer.scales[0]).clone();
+        scale.name = "Pentatonic".to_string();
+        let renamed = renames_used_scale(&sequencer, Some(0), &scale.name);
+        assert!(renamed);
+        sequencer.replace_scale(Some(0), scale);
+        let paths = (scale_path.to_string_lossy(), sequence_path.to_string_lossy());
+        save_scale_files(&paths.0, &paths.1, &sequencer, renamed).unwrap();
+
+        let loaded = load(&scale_path, &sequence_path);
+        assert_eq!(loaded.sequences[0].scale().name, "Pentatonic");
+        assert_eq!(loaded.sequences[0].notes[0].note_index, 3);
+        fs::remove_file(scale_path).unwrap();
+        fs::remove_file(sequence_path).unwrap();
+    }
+
+    #[test]
+    fn only_renaming_a_used_scale_writes_the_sequences() {
+        let scale = |name: &str| {
+            Arc::new(Scale {
+                name: name.to_string(),
+                steps: vec![0f64],
+                note_names: vec!["a".to_string()],
+            })
+        };
+        let note = Note::new(scale("Used"), 0, 5, scale_browser::preview_duration(), 100);
+        let sequence = Sequence::new("Intro".to_string(), scale("Used"), vec![note]);
+        let sequencer = Sequencer::new(vec![sequence], vec![scale("Used"), scale("Unused")]);
+        assert!(renames_used_scale(&sequencer, Some(0), "Other"));
+        assert!(!renames_used_scale(&sequencer, Some(0), "Used"));
+        assert!(!renames_used_scale(&sequencer, Some(1), "Other"));
+        assert!(!renames_used_scale(&sequencer, None, "Other"));
+    }
+}
@@ -1,10 +1,19 @@
+use crate::clock::MAX_NOTE_BEATS;
+use crate::history::EditCommand;
 use crate::init_sequencer;
-use crate::note::Note;
+use crate::json::{
+    save_project_to_json_file, save_scales_to_json_file, save_sequences_to_json_file,
+};
+use crate::note::{Note, NoteDuration};
+use crate::sequencer::{RetuneMode, Sequence};
+use crate::tui::clipboard::Clipboard;
 use crate::tui::entities::{
     App, MainMenuItem, Menu, MenuItemList, SequencerMenuItem, SequencerMenuSelectedItem,
 };
 use crate::tui::error_handling::MidiSequencerTUIResult;
-use crossterm::event::{KeyCode, KeyEvent, KeyEventKind};
+use crate::tui::file_watcher;
+use crate::tui::menus::{file_browser, note_inspector, save_as, scale_browser, scale_editor};
+use crossterm::event::{KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
 use ratatui::layout::{Alignment, Constraint, Direction, Layout, Rect};
 use ratatui::prelude::{Color, Line, Style, Text};
 use ratatui::text::ToSpan;
@@ -13,6 +22,9 @@ use ratatui::Frame;
 use std::io;
 use std::sync::atomic::Ordering;
 
+/// Change of duration of a note with `b` held, in beats.
+const DURATION_STEP: f64 = 0.125;
+
 pub fn draw(frame: &mut Frame, app: &mut App) {
     let outer_layout = Layout::default()
         .direction(Direction::Vertical)
@@ -31,8 +43,10 @@ pub fn draw(frame: &mut Frame, app: &mut App) {
             false => "OFF",
         }),
         ListItem::new(app.sequencer.lock().unwrap().current_scale_name()),
-        ListItem::new("Save sequence"),
-        ListItem::new("Load sequence"),
+        ListItem::new("Save"),
+        ListItem::new("Save sequences as..."),
+        ListItem::new("Save project as..."),
+        ListItem::new("Load"),
     ];
 
     menus.push(ListItem::new("Exit"));
@@ -41,7 +55,8 @@ pub fn draw(frame: &mut Frame, app: &mut App) {
         .block(
             Block::bordered()
                 .style(Style::default().fg(Color::White))
-                .title(" Options ".to_span().into_centered_line()),
+                .title(" Options ".to_span().into_centered_line())
+                .title_bottom(" ^Z undo ^Y redo ".to_span().into_centered_line()),
         )
         .style(Style::default().fg(Color::LightBlue))
         .highlight_style(Style::default().fg(Color::Black).bg(Color::LightBlue));
@@ -58,11 +73,27 @@ pub fn draw(frame: &mut Frame, app: &mut App) {
     frame.render_stateful_widget(list, inner_upper_layout[0], &mut menu_state);
 
     let current_sequence_name = app.sequencer.lock().unwrap().current_sequence_name();
-    let sequence_block_title = format!(" {} ", current_sequence_name);
+    let sequence_block_title = match app.sequencer.lock().unwrap().current_sequence().generator {
+        Some(_) => format!(" {} (generative, g: new melody) ", current_sequence_name),
+        None => format!(" {} ", current_sequence_name),
+    };
 
     let sequence_block = Block::bordered()
         .style(Style::default().fg(Color::White))
-        .title(sequence_block_title.to_span().into_centered_line());
+        .title(sequence_block_title.to_span().into_centered_line())
+        .title(
+            Line::from(
+                " ⇧←/→ select  y/x/p copy/cut/paste  o/O insert/duplicate  Del remove  \
+                  [/] length ",
+            )
+            .right_aligned(),
+        )
+        .title_bottom(
+            " t/T transpose  i invert  r retrograde  </> rotate  a/d longer/shorter  f fold  \
+              u/U retune by pitch/degree "
+                .to_span()
+                .into_centered_line(),
+        );
 
     let sequence_area = sequence_block.inner(inner_upper_layout[1]);
     frame.render_widget(sequence_block, inner_upper_layout[1]);
@@ -76,9 +107,14 @@ pub fn draw(frame: &mut Frame, app: &mut App) {
     let mut x = sequence_area.left() + spacing;
     let base_x = x;
 
+    let selection = selected_steps(app);
     let sequencer = app.sequencer.lock().unwrap();
     let notes = sequencer.current_sequence().notes;
     let current_note_index = sequencer.current_note_index;
+    let retune_errors = match &app.retune_errors {
+        Some((i, errors)) if *i == sequencer.current_sequence_index => Some(errors.clone()),
+        _ => None,
+    };
     drop(sequencer);
 
     for (i, note) in notes.iter().enumerate() {
@@ -90,12 +126,12 @@ pub fn draw(frame: &mut Frame, app: &mut App) {
             Color::White
         };
 
-        let selected = if let SequencerMenuSelectedItem::Note { item } = get_selected(app)
+        let selected = match get_selected(app)
             .unwrap_or_default_val_and_display_err(app, SequencerMenuSelectedItem::default())
         {
-            Some(item)
-        } else {
-            None
+            SequencerMenuSelectedItem::Note { item }
+            | SequencerMenuSelectedItem::Inspector { item, .. } => Some(item),
+            _ => None,
         };
 
         let bg_color = match selected {
@@ -113,15 +149,20 @@ pub fn draw(frame: &mut Frame, app: &mut App) {
                     } else {
                         Color::LightBlue
                     }
+                } else if selection.is_some_and(|(first, last)| (first..=last).contains(&i)) {
+                    Color::Blue
                 } else {
                     Color::Reset
                 }
             }
         };
 
-        let note_block = Block::bordered()
+        let mut note_block = Block::bordered()
             .border_style(Style::default().fg(border_color))
             .style(Style::default().bg(bg_color));
+        if let Some(error) = retune_errors.as_ref().and_then(|errors| errors.get(i)) {
+            note_block = note_block.title_bottom(Line::from(format!("{:+.1}c", error)));
+        }
 
         let text = Text::from(vec![
             Line::from(note.get_common_name()).centered(),
@@ -142,16 +183,36 @@ pub fn draw(frame: &mut Frame, app: &mut App) {
         }
     }
 
-    let playlist_block = Block::bordered()
+    note_inspector::draw(frame, app, sequence_area);
+    scale_browser::draw(frame, app, sequence_area);
+    scale_editor::draw(frame, app, sequence_area);
+    save_as::draw(frame, app, sequence_area);
+    file_browser::draw(frame, app, sequence_area);
+
+    let mut playlist_block = Block::bordered()
         .style(Style::default().fg(Color::White))
         .title(" Playlist ".to_span().into_centered_line());
 
     let sequencer = app.sequencer.lock().unwrap();
     let sequences = sequencer.sequences.clone();
     let current_sequence_index = sequencer.current_sequence_index;
+    let queued_sequence = sequencer.queued_sequence;
 
     drop(sequencer);
 
+    let selected_playlist_item = match get_selected(app) {
+        Ok(SequencerMenuSelectedItem::PlaylistItem { item }) => Some(item),
+        _ => None,
+    };
+    if selected_playlist_item.is_some() {
+        playlist_block = playlist_block.title_bottom(
+            " Enter play  ⇧←/→ move  +/- repeat  d duplicate  i insert  Del remove  \
+              y/x/p copy/cut/paste "
+                .to_span()
+                .into_centered_line(),
+        );
+    }
+
     let rect_width = 20;
     let playlist_area = playlist_block.inner(outer_layout[1]);
     frame.render_widget(playlist_block, outer_layout[1]);
@@ -163,13 +224,20 @@ pub fn draw(frame: &mut Frame, app: &mut App) {
 
         let border_color = if i == current_sequence_index {
             Color::Red
+        } else if Some(i) == queued_sequence {
+            Color::Yellow
         } else {
             Color::White
         };
+        let bg_color = if Some(i) == selected_playlist_item {
+            Color::LightBlue
+        } else {
+            Color::Reset
+        };
 
         let sequence_block = Block::bordered()
             .border_style(Style::default().fg(border_color))
-            .style(Style::default().fg(Color::White).bg(Color::Reset));
+            .style(Style::default().fg(Color::White).bg(bg_color));
 
         let text = Text::from(vec![
             Line::from(sequence.name.clone()).centered(),
@@ -191,7 +259,7 @@ pub fn draw(frame: &mut Frame, app: &mut App) {
 fn get_selected(app: &App) -> Result<SequencerMenuSelectedItem, io::Error> {
     match &app.current_menu {
         Menu::Sequencer { selected_menu } => Ok(selected_menu.clone()),
-        _ => Err(io::Error::new(io::ErrorKind::Other, "Out of bounds")),
+        _ => Err(io::Error::other("Out of bounds")),
     }
 }
 
@@ -201,8 +269,12 @@ pub fn handle_key(app: &mut App, key_event: KeyEvent) -> Result<(), io::Error> {
     {
         SequencerMenuSelectedItem::SubMenuItem { .. } => handle_key_submenu(app, key_event),
         SequencerMenuSelectedItem::Note { .. } => handle_key_notes(app, key_event),
+        SequencerMenuSelectedItem::Inspector { .. } => note_inspector::handle_key(app, key_event),
         SequencerMenuSelectedItem::PlaylistItem { .. } => handle_key_playlist(app, key_event),
-        SequencerMenuSelectedItem::Scale { .. } => Ok(()),
+        SequencerMenuSelectedItem::Scale { .. } => scale_browser::handle_key(app, key_event),
+        SequencerMenuSelectedItem::ScaleEditor { .. } => scale_editor::handle_key(app, key_event),
+        SequencerMenuSelectedItem::SaveAs { .. } => save_as::handle_key(app, key_event),
+        SequencerMenuSelectedItem::FileBrowser { .. } => file_browser::handle_key(app, key_event),
     }
 }
 
@@ -226,10 +298,13 @@ fn on_enter_submenu(app: &mut App) -> Result<(), io::Error> {
         SequencerMenuSelectedItem::SubMenuItem { item } => match item {
             SequencerMenuItem::OnOff => handle_on_off(app),
             SequencerMenuItem::Scale => handle_scale_menu(app),
+            SequencerMenuItem::Save => handle_save(app),
+            SequencerMenuItem::SaveAs => save_as::open(app, false),
+            SequencerMenuItem::SaveProjectAs => save_as::open(app, true),
+            SequencerMenuItem::Load => file_browser::open(app),
             SequencerMenuItem::Exit => handle_exit(app),
-            _ => Ok(()),
         },
-        _ => Err(io::Error::new(io::ErrorKind::Other, "Out of bounds")),
+        _ => Err(io::Error::other("Out of bounds")),
     }
 }
 
@@ -247,7 +322,7 @@ fn on_up_submenu(app: &mut App) -> Result<(), io::Error> {
             }
             Ok(())
         }
-        _ => Err(io::Error::new(io::ErrorKind::Other, "Out of bounds")),
+        _ => Err(io::Error::other("Out of bounds")),
     }
 }
 
@@ -265,7 +340,7 @@ fn on_down_submenu(app: &mut App) -> Result<(), io::Error> {
             }
             Ok(())
         }
-        _ => Err(io::Error::new(io::ErrorKind::Other, "Out of bounds")),
+        _ => Err(io::Error::other("Out of bounds")),
     }
 }
 
@@ -286,7 +361,39 @@ fn handle_on_off(app: &mut App) -> Result<(), io::Error> {
 }
 
 fn handle_scale_menu(app: &mut App) -> Result<(), io::Error> {
-    todo!()
+    scale_browser::open(app)
+}
+
+fn handle_save(app: &mut App) -> Result<(), io::Error> {
+    match app.project_path.clone() {
+        Some(path) => save_project(app, &path),
+        None => {
+            let path = app.sequence_path.clone();
+            save(app, &path)
+        }
+    }
+}
+
+/// Writes the sequences to `path` and the scales they refer to by name to the scale file.
+/// When a project is loaded, its scales stay in it: only the sequences are written.
+pub fn save(app: &mut App, path: &str) -> Result<(), io::Error> {
+    let sequencer = app.sequencer.lock().unwrap();
+    if app.project_path.is_none() {
+        save_scales_to_json_file(&app.scale_path, &sequencer.scales)?;
+    }
+    save_sequences_to_json_file(path, &sequencer.sequences)?;
+    drop(sequencer);
+    file_watcher::saved(app);
+    Ok(())
+}
+
+/// Writes the scales, sequences and settings to the project file at `path`.
+pub fn save_project(app: &mut App, path: &str) -> Result<(), io::Error> {
+    let sequencer = app.sequencer.lock().unwrap();
+    save_project_to_json_file(path, &sequencer)?;
+    drop(sequencer);
+    file_watcher::saved(app);
+    Ok(())
 }
 
 fn handle_exit(app: &mut App) -> Result<(), io::Error> {
@@ -298,18 +405,148 @@ fn handle_exit(app: &mut App) -> Result<(), io::Error> {
 }
 
 fn handle_key_notes(app: &mut App, key_event: KeyEvent) -> Result<(), io::Error> {
-    if key_event.kind == KeyEventKind::Press {
-        return match key_event.code {
-            KeyCode::Left => on_left_notes(app),
-            KeyCode::Right => on_right_notes(app),
-            KeyCode::Up => on_up_notes(app),
-            KeyCode::Down => on_down_notes(app),
-            _ => Ok(()),
-        };
+    if key_event.kind != KeyEventKind::Press {
+        return Ok(());
+    }
+    let shift = key_event.modifiers.contains(KeyModifiers::SHIFT);
+    let extending = shift && matches!(key_event.code, KeyCode::Left | KeyCode::Right);
+    if extending && app.selection_anchor.is_none() {
+        app.selection_anchor = selected_steps(app).map(|(first, _)| first);
+    }
+    let result = match key_event.code {
+        KeyCode::Left if shift => extend_selection(app, false),
+        KeyCode::Right if shift => extend_selection(app, true),
+        KeyCode::Left => on_left_notes(app),
+        KeyCode::Right => on_right_notes(app),
+        KeyCode::Up => on_up_notes(app, shift),
+        KeyCode::Down => on_down_notes(app, shift),
+        KeyCode::Enter => on_enter_notes(app),
+        KeyCode::Char('g') => on_generate_notes(app),
+        KeyCode::Insert | KeyCode::Char('o') => edit_steps(app, |sequence, item| {
+            let note = new_step_like(&sequence.notes[item]);
+            sequence.notes.insert(item + 1, note);
+            item + 1
+        }),
+        KeyCode::Char('O') => edit_steps(app, |sequence, item| {
+            let note = sequence.notes[item].clone();
+            sequence.notes.insert(item + 1, note);
+            item + 1
+        }),
+        KeyCode::Delete | KeyCode::Backspace => edit_steps(app, |sequence, item| {
+            if sequence.notes.len() > 1 {
+                sequence.notes.remove(item);
+            }
+            item
+        }),
+        KeyCode::Char(']') => edit_steps(app, |sequence, item| {
+            let note = new_step_like(&sequence.notes[sequence.notes.len() - 1]);
+            sequence.notes.push(note);
+            item
+        }),
+        KeyCode::Char('[') => edit_steps(app, |sequence, item| {
+            if sequence.notes.len() > 1 {
+                sequence.notes.pop();
+            }
+            item
+        }),
+        KeyCode::Char('t') => transform_sequence(app, |sequence, _| sequence.transpose(1)),
+        KeyCode::Char('T') => transform_sequence(app, |sequence, _| sequence.transpose(-1)),
+        KeyCode::Char('i') => transform_sequence(app, |sequence, pivot| sequence.invert(pivot)),
+        KeyCode::Char('r') => transform_sequence(app, |sequence, _| sequence.retrograde()),
+        KeyCode::Char('<') => transform_sequence(app, |sequence, _| sequence.rotate(-1)),
+        KeyCode::Char('>') => transform_sequence(app, |sequence, _| sequence.rotate(1)),
+        KeyCode::Char('a') => transform_sequence(app, |sequence, _| {
+            sequence.scale_durations(2f64)
+        }),
+        KeyCode::Char('d') => transform_sequence(app, |sequence, _| {
+            sequence.scale_durations(0.5)
+        }),
+        KeyCode::Char('u') => retune_to_next_scale(app, RetuneMode::NearestPitch),
+        KeyCode::Char('U') => retune_to_next_scale(app, RetuneMode::Degree),
+        KeyCode::Char('f') => transform_sequence(app, |sequence, pivot| {
+            let scale_len = sequence.scale().steps.len() as i32;
+            sequence.fold_octaves(pivot, pivot + scale_len - 1)
+        }),
+        KeyCode::Char('y') => copy_steps(app),
+        KeyCode::Char('x') => cut_steps(app),
+        KeyCode::Char('p') => paste_steps(app),
+        _ => Ok(()),
+    };
+    // Copying keeps the selection, so that it can still be extended or cut
+    if !extending && key_event.code != KeyCode::Char('y') {
+        app.selection_anchor = None;
+    }
+    result
+}
+
+fn on_enter_notes(app: &mut App) -> Result<(), io::Error> {
+    match get_selected(app)
+        .unwrap_or_default_val_and_display_err(app, SequencerMenuSelectedItem::default())
+    {
+        SequencerMenuSelectedItem::Note { item } => note_inspector::open(app, item),
+        _ => Err(io::Error::other("Out of bounds")),
+    }
+}
+
+fn on_generate_notes(app: &mut App) -> Result<(), io::Error> {
+    let mut sequencer = app.sequencer.lock().unwrap();
+    let current_sequence_i = sequencer.current_sequence_index;
+    if sequencer.sequences[current_sequence_i].generator.is_some() {
+        app.history
+            .record(EditCommand::sequence(&sequencer, current_sequence_i));
+        sequencer.sequences[current_sequence_i].generate_melody();
     }
     Ok(())
 }
 
+/// Applies a whole-sequence transformation to the current sequence. The transformation gets
+/// the degree of the selected note, which inversion and folding are made around.
+fn transform_sequence(
+    app: &mut App,
+    transformation: impl FnOnce(&mut Sequence, i32),
+) -> Result<(), io::Error> {
+    if app.sequencer.lock().unwrap().started {
+        return Ok(());
+    }
+    match get_selected(app)
+        .unwrap_or_default_val_and_display_err(app, SequencerMenuSelectedItem::default())
+    {
+        SequencerMenuSelectedItem::Note { item } => {
+            let mut sequencer = app.sequencer.lock().unwrap();
+            let current_sequence_i = sequencer.current_sequence_index;
+            app.history
+                .record(EditCommand::sequence(&sequencer, current_sequence_i));
+            let sequence = &mut sequencer.sequences[current_sequence_i];
+            // The sequence may have been reloaded with fewer notes while playing
+            let item = item.min(sequence.notes.len() - 1);
+            let pivot = sequence.notes[item].degree();
+            transformation(sequence, pivot);
+            app.retune_errors = None;
+            Ok(())
+        }
+        _ => Err(io::Error::other("Out of bounds")),
+    }
+}
+
+/// Moves the current sequence to the scale following its own in the loaded scales, and keeps
+/// the cents error of each note to show it in the grid.
+fn retune_to_next_scale(app: &mut App, mode: RetuneMode) -> Result<(), io::Error> {
+    let mut sequencer = app.sequencer.lock().unwrap();
+    let current_scale = sequencer.current_scale();
+    let scale_i = sequencer
+        .scales
+        .iter()
+        .position(|s| s.name == current_scale.name)
+        .map_or(0, |i| (i + 1) % sequencer.scales.len());
+    let scale = sequencer.scales[scale_i].clone();
+    let current_sequence_i = sequencer.current_sequence_index;
+    app.history
+        .record(EditCommand::sequence(&sequencer, current_sequence_i));
+    let errors = sequencer.sequences[current_sequence_i].retune(scale, mode);
+    app.retune_errors = Some((current_sequence_i, errors));
+    Ok(())
+}
+
 fn on_left_notes(app: &mut App) -> Result<(), io::Error> {
     match get_selected(app)
         .unwrap_or_default_val_and_display_err(app, SequencerMenuSelectedItem::default())
@@ -326,7 +563,7 @@ fn on_left_notes(app: &mut App) -> Result<(), io::Error> {
             };
             Ok(())
         }
-        _ => Err(io::Error::new(io::ErrorKind::Other, "Out of bounds")),
+        _ => Err(io::Error::other("Out of bounds")),
     }
 }
 
@@ -342,12 +579,47 @@ fn on_right_notes(app: &mut App) -> Result<(), io::Error> {
             }
             Ok(())
         }
-        _ => Err(io::Error::new(io::ErrorKind::Other, "Out of bounds")),
+        _ => Err(io::Error::other("Out of bounds")),
     }
 }
 
-fn on_up_notes(app: &mut App) -> Result<(), io::Error> {
-    if app.held_keys.is_empty() || app.sequencer.lock().unwrap().started {
+fn on_up_notes(app: &mut App, shift: bool) -> Result<(), io::Error> {
+    if app.held_keys.is_empty() {
+        return Ok(());
+    }
+    change_selected_note(app, 1, shift)
+}
+
+fn on_down_notes(app: &mut App, shift: bool) -> Result<(), io::Error> {
+    if app.held_keys.is_empty() {
+        let current_sequence_index = app.sequencer.lock().unwrap().current_sequence_index;
+        return select_playlist_item(app, current_sequence_index);
+    }
+    change_selected_note(app, -1, shift)
+}
+
+/// Changes the parameter of the selected note picked by the held key: `v` velocity,
+/// `b` duration and `n` pitch. Shift makes bigger changes: 10 velocity steps, doubling or
+/// halving the duration, or an octave.
+fn change_selected_note(app: &mut App, direction: i32, shift: bool) -> Result<(), io::Error> {
+    let held_keys = app.held_keys.clone();
+    edit_selected_note(app, |note| {
+        if held_keys.contains(&KeyCode::Char('v')) {
+            change_velocity_of_note(note, direction * if shift { 10 } else { 1 });
+        } else if held_keys.contains(&KeyCode::Char('b')) {
+            change_duration_of_note(note, direction, shift);
+        } else if held_keys.contains(&KeyCode::Char('n')) {
+            let degrees = match shift {
+                true => note.scale.steps.len() as i32,
+                false => 1,
+            };
+            note.set_degree(note.degree() + direction * degrees);
+        }
+    })
+}
+
+fn edit_selected_note(app: &mut App, edit: impl FnOnce(&mut Note)) -> Result<(), io::Error> {
+    if app.sequencer.lock().unwrap().started {
         return Ok(());
     }
     match get_selected(app)
@@ -356,19 +628,39 @@ fn on_up_notes(app: &mut App) -> Result<(), io::Error> {
         SequencerMenuSelectedItem::Note { item } => {
             let mut sequencer = app.sequencer.lock().unwrap();
             let current_sequence_i = sequencer.current_sequence_index;
+            app.history
+                .record(EditCommand::sequence(&sequencer, current_sequence_i));
             let sequence = &mut sequencer.sequences[current_sequence_i];
-            let note = &mut sequence.notes[item];
-            if app.held_keys.contains(&KeyCode::Char('n')) {
-                increment_tonality_of_note(note);
-            }
+            let item = item.min(sequence.notes.len() - 1);
+            edit(&mut sequence.notes[item]);
             Ok(())
         }
-        _ => Err(io::Error::new(io::ErrorKind::Other, "Out of bounds")),
+        _ => Err(io::Error::other("Out of bounds")),
     }
 }
 
-fn on_down_notes(app: &mut App) -> Result<(), io::Error> {
-    if app.held_keys.is_empty() || app.sequencer.lock().unwrap().started {
+fn change_velocity_of_note(note: &mut Note, amount: i32) {
+    note.velocity = (note.velocity as i32 + amount).clamp(1, 127) as u8;
+}
+
+/// Moves the duration by an eighth of a beat, or doubles or halves it. It stays between one
+/// clock tick and `MAX_NOTE_BEATS`.
+fn change_duration_of_note(note: &mut Note, direction: i32, shift: bool) {
+    let duration = note.duration.duration;
+    let duration = match (shift, direction > 0) {
+        (true, true) => duration * 2f64,
+        (true, false) => duration / 2f64,
+        (false, _) => duration + direction as f64 * DURATION_STEP,
+    };
+    note.duration.duration = duration.clamp(1f64 / 24f64, MAX_NOTE_BEATS);
+}
+
+/// Edits the steps of the current sequence, and returns the step to select afterwards.
+fn edit_steps(
+    app: &mut App,
+    edit: impl FnOnce(&mut Sequence, usize) -> usize,
+) -> Result<(), io::Error> {
+    if app.sequencer.lock().unwrap().started {
         return Ok(());
     }
     match get_selected(app)
@@ -377,37 +669,276 @@ fn on_down_notes(app: &mut App) -> Result<(), io::Error> {
         SequencerMenuSelectedItem::Note { item } => {
             let mut sequencer = app.sequencer.lock().unwrap();
             let current_sequence_i = sequencer.current_sequence_index;
+            app.history
+                .record(EditCommand::sequence(&sequencer, current_sequence_i));
             let sequence = &mut sequencer.sequences[current_sequence_i];
-            let note = &mut sequence.notes[item];
-            if app.held_keys.contains(&KeyCode::Char('n')) {
-                decrement_tonality_of_note(note);
-            }
+            let item = item.min(sequence.notes.len() - 1);
+            let selected = edit(sequence, item).min(sequence.notes.len() - 1);
+            drop(sequencer);
+            app.retune_errors = None;
+            app.current_menu = Menu::Sequencer {
+                selected_menu: SequencerMenuSelectedItem::Note { item: selected },
+            };
             Ok(())
         }
-        _ => Err(io::Error::new(io::ErrorKind::Other, "Out of bounds")),
+        _ => Err(io::Error::other("Out of bounds")),
     }
 }
 
-// TODO: Increase by octave when holding shift
-fn increment_tonality_of_note(note: &mut Note) {
-    let scale_len = note.scale.steps.len();
-    note.note_index = (note.note_index + 1) % scale_len;
-    if note.note_index == 0 {
-        note.octave += 1;
+/// A plain note with the pitch, duration and velocity of `note`.
+fn new_step_like(note: &Note) -> Note {
+    Note::new(
+        note.scale.clone(),
+        note.note_index,
+        note.octave,
+        note.duration.clone(),
+        note.velocity,
+    )
+}
+
+/// The first and last of the selected steps of the current sequence.
+fn selected_steps(app: &App) -> Option<(usize, usize)> {
+    let SequencerMenuSelectedItem::Note { item } = get_selected(app).ok()? else {
+        return None;
+    };
+    let len = app.sequencer.lock().unwrap().current_sequence_length();
+    step_range(item, app.selection_anchor, len)
+}
+
+/// The steps from `item` to `anchor`, in a sequence of `len` steps. The selection can go past
+/// the end of the sequence when it changes while playing: it is cut to the last step.
+fn step_range(item: usize, anchor: Option<usize>, len: usize) -> Option<(usize, usize)> {
+    let last = len.checked_sub(1)?;
+    let item = item.min(last);
+    let anchor = anchor.unwrap_or(item).min(last);
+    Some((anchor.min(item), anchor.max(item)))
+}
+
+/// Moves the selected step, keeping the other end of the selection in place.
+fn extend_selection(app: &mut App, forward: bool) -> Result<(), io::Error> {
+    match get_selected(app)? {
+        SequencerMenuSelectedItem::Note { item } => {
+            let last = app.sequencer.lock().unwrap().current_sequence_length() - 1;
+            let item = match forward {
+                true => (item + 1).min(last),
+                false => item.saturating_sub(1),
+            };
+            app.current_menu = Menu::Sequencer {
+                selected_menu: SequencerMenuSelectedItem::Note { item },
+            };
+            Ok(())
+        }
+        _ => Err(io::Error::other("Out of bounds")),
     }
 }
 
-fn decrement_tonality_of_note(note: &mut Note) {
-    let scale_len = note.scale.steps.len();
-    note.note_index = (note.note_index + scale_len - 1) % scale_len;
-    if note.note_index == scale_len - 1 {
-        note.octave -= 1;
+fn copy_steps(app: &mut App) -> Result<(), io::Error> {
+    let Some((first, last)) = selected_steps(app) else {
+        return Ok(());
+    };
+    let sequence = app.sequencer.lock().unwrap().current_sequence();
+    // The sequence can change between the two locks
+    let Some(notes) = sequence.notes.get(first..=last) else {
+        return Ok(());
+    };
+    app.clipboard = Some(Clipboard::Steps(notes.to_vec()));
+    Ok(())
+}
+
+/// Copies the selected steps and removes them, unless they are the whole sequence.
+fn cut_steps(app: &mut App) -> Result<(), io::Error> {
+    let Some((first, last)) = selected_steps(app) else {
+        return Ok(());
+    };
+    let mut cut = None;
+    edit_steps(app, |sequence, _| {
+        let Some(notes) = sequence.notes.get(first..=last) else {
+            return first;
+        };
+        cut = Some(notes.to_vec());
+        if last - first + 1 < sequence.notes.len() {
+            sequence.notes.drain(first..=last);
+        }
+        first
+    })?;
+    if let Some(notes) = cut {
+        app.clipboard = Some(Clipboard::Steps(notes));
     }
+    Ok(())
+}
+
+/// Inserts the copied steps after the selected one. Steps copied from a sequence using
+/// another scale are moved into the scale of the current sequence with the retune mode of
+/// the scale browser.
+fn paste_steps(app: &mut App) -> Result<(), io::Error> {
+    let Some(clipboard) = &app.clipboard else {
+        return Ok(());
+    };
+    let scale = app.sequencer.lock().unwrap().current_scale();
+    let notes = clipboard.notes_in(&scale, app.retune_mode);
+    edit_steps(app, |sequence, item| {
+        let count = notes.len();
+        sequence.notes.splice(item + 1..item + 1, notes);
+        item + count
+    })
 }
 
 fn handle_key_playlist(app: &mut App, key_event: KeyEvent) -> Result<(), io::Error> {
-    if key_event.kind == KeyEventKind::Press {}
-    todo!();
+    if key_event.kind != KeyEventKind::Press {
+        return Ok(());
+    }
+    let item = match get_selected(app)? {
+        SequencerMenuSelectedItem::PlaylistItem { item } => item,
+        _ => return Err(io::Error::other("Out of bounds")),
+    };
+    let shift = key_event.modifiers.contains(KeyModifiers::SHIFT);
+    let mut sequencer = app.sequencer.lock().unwrap();
+    let last = sequencer.sequences.len() - 1;
+    // The playlist may have been reloaded with fewer sequences
+    let item = item.min(last);
+    let selected = match key_event.code {
+        KeyCode::Up => {
+            drop(sequencer);
+            app.current_menu = Menu::Sequencer {
+                selected_menu: SequencerMenuSelectedItem::Note { item: 0 },
+            };
+            return Ok(());
+        }
+        KeyCode::Left if shift && item > 0 => {
+            app.history.record(EditCommand::all(&sequencer));
+            sequencer.swap_sequences(item, item - 1);
+            item - 1
+        }
+        KeyCode::Right if shift && item < last => {
+            app.history.record(EditCommand::all(&sequencer));
+            sequencer.swap_sequences(item, item + 1);
+            item + 1
+        }
+        KeyCode::Left => item.saturating_sub(1),
+        KeyCode::Right => (item + 1).min(last),
+        KeyCode::Char('+') => {
+            app.history.record(EditCommand::sequence(&sequencer, item));
+            let sequence = &mut sequencer.sequences[item];
+            sequence.repeat = (sequence.repeat + 1).min(u8::MAX as usize);
+            item
+        }
+        KeyCode::Char('-') => {
+            app.history.record(EditCommand::sequence(&sequencer, item));
+            let sequence = &mut sequencer.sequences[item];
+            sequence.repeat = sequence.repeat.saturating_sub(1);
+            item
+        }
+        KeyCode::Char('d') => {
+            app.history.record(EditCommand::all(&sequencer));
+            let mut copy = sequencer.sequences[item].clone();
+            copy.name = format!("{} copy", copy.name);
+            sequencer.insert_sequence(item + 1, copy);
+            item + 1
+        }
+        KeyCode::Char('i') => {
+            app.history.record(EditCommand::all(&sequencer));
+            let sequence = &sequencer.sequences[item];
+            let octave = sequence.notes.first().map_or(5, |n| n.octave);
+            let duration = NoteDuration { duration: 1f64 };
+            let note = Note::new(sequence.scale(), 0, octave, duration, 100);
+            let name = "New sequence".to_string();
+            let new_sequence = Sequence::new(name, sequence.scale(), vec![note]);
+            sequencer.insert_sequence(item + 1, new_sequence);
+            item + 1
+        }
+        KeyCode::Delete | KeyCode::Backspace if last > 0 => {
+            app.history.record(EditCommand::all(&sequencer));
+            sequencer.remove_sequence(item);
+            item.min(sequencer.sequences.len() - 1)
+        }
+        KeyCode::Char('y') => {
+            app.clipboard = Some(Clipboard::Sequence(sequencer.sequences[item].clone()));
+            item
+        }
+        KeyCode::Char('x') if last > 0 => {
+            app.history.record(EditCommand::all(&sequencer));
+            let sequence = sequencer.remove_sequence(item);
+            app.clipboard = sequence.map(Clipboard::Sequence);
+            item.min(sequencer.sequences.len() - 1)
+        }
+        KeyCode::Char('p') => match &app.clipboard {
+            Some(clipboard) => {
+                app.history.record(EditCommand::all(&sequencer));
+                sequencer.insert_sequence(item + 1, clipboard.to_sequence());
+                item + 1
+            }
+            None => item,
+        },
+        KeyCode::Enter => {
+            sequencer.queue_sequence(item);
+            item
+        }
+        _ => item,
+    };
+    drop(sequencer);
+    // The cents errors are kept by index, which the playlist edits may change
+    let keeps_indices = matches!(
+        key_event.code,
+        KeyCode::Left | KeyCode::Right | KeyCode::Enter | KeyCode::Char('y')
+    );
+    if !keeps_indices || shift {
+        app.retune_errors = None;
+    }
+    select_playlist_item(app, selected)
+}
+
+fn select_playlist_item(app: &mut App, item: usize) -> Result<(), io::Error> {
+    app.current_menu = Menu::Sequencer {
+        selected_menu: SequencerMenuSelectedItem::PlaylistItem { item },
+    };
+    Ok(())
+}
+
+/// Undoes or redoes an edit made in any of the editing screens, then keeps the selection
+/// within the restored sequences and scales.
+pub fn undo(app: &mut App, redo: bool) -> Result<(), io::Error> {
+    let mut sequencer = app.sequencer.lock().unwrap();
+    let changed = match redo {
+        true => app.history.redo(&mut sequencer),
+        false => app.history.undo(&mut sequencer),
+    };
+    drop(sequencer);
+    if changed {
+        keep_selection_in_range(app);
+    }
+    Ok(())
+}
+
+/// Clears the selection range and the retune errors, and moves the selected note, sequence
+/// or scale back within the loaded ones, after they were replaced by an undo or a reload.
+pub(crate) fn keep_selection_in_range(app: &mut App) {
+    let sequencer = app.sequencer.lock().unwrap();
+    let notes_len = sequencer.current_sequence_length();
+    let sequences_len = sequencer.sequences.len();
+    let scales_len = sequencer.scales.len();
+    drop(sequencer);
+
+    app.retune_errors = None;
+    app.selection_anchor = None;
+    let Menu::Sequencer { selected_menu } = &app.current_menu else {
+        return;
+    };
+    let selected_menu = match selected_menu.clone() {
+        SequencerMenuSelectedItem::Note { item }
+        | SequencerMenuSelectedItem::Inspector { item, .. } => SequencerMenuSelectedItem::Note {
+            item: item.min(notes_len - 1),
+        },
+        SequencerMenuSelectedItem::PlaylistItem { item } => {
+            SequencerMenuSelectedItem::PlaylistItem {
+                item: item.min(sequences_len - 1),
+            }
+        }
+        SequencerMenuSelectedItem::Scale { item } => SequencerMenuSelectedItem::Scale {
+            item: item.min(scales_len - 1),
+        },
+        selected_menu => selected_menu,
+    };
+    app.current_menu = Menu::Sequencer { selected_menu };
 }
 
 fn start_sequencer(app: &mut App) -> Result<(), io::Error> {
@@ -425,3 +956,22 @@ pub fn move_to(app: &mut App) -> Result<(), io::Error> {
         Err(err) => Err(err),
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn step_range_goes_from_the_anchor_to_the_selected_step() {
+        assert_eq!(step_range(3, Some(1), 8), Some((1, 3)));
+        assert_eq!(step_range(1, Some(3), 8), Some((1, 3)));
+        assert_eq!(step_range(2, None, 8), Some((2, 2)));
+    }
+
+    #[test]
+    fn step_range_is_cut_to_a_shorter_current_sequence() {
+        assert_eq!(step_range(5, Some(7), 3), Some((2, 2)));
+        assert_eq!(step_range(6, Some(1), 4), Some((1, 3)));
+        assert_eq!(step_range(0, None, 0), None);
+    }
+}
@@ -1,14 +1,206 @@
-use crate::tui::entities::App;
-use crossterm::event::KeyEvent;
+use crate::arpeggiator::ARP_RATES;
+use crate::clock::Tempo;
+use crate::tui::entities::{App, MainMenuItem, Menu, MenuItemList, SettingsMenuItem};
+use crossterm::event::{KeyCode, KeyEvent, KeyEventKind};
+use ratatui::layout::{Constraint, Direction, Layout};
+use ratatui::prelude::{Color, Style};
+use ratatui::text::ToSpan;
+use ratatui::widgets::{Block, List, ListItem, ListState};
 use ratatui::Frame;
 use std::io;
 
-pub fn draw(frame: &mut Frame) {}
+/// Rates offered for the pitch bend updates of glides and modulation, in Hz.
+const PITCH_UPDATE_RATES: [u32; 6] = [50, 100, 200, 300, 500, 1000];
 
-pub fn handle_key(app: &mut App, key: KeyEvent) -> Result<(), io::Error> {
+pub fn draw(frame: &mut Frame, app: &App) {
+    let outer_layout = Layout::default()
+        .direction(Direction::Vertical)
+        .margin(1)
+        .constraints([Constraint::Percentage(100)].as_ref())
+        .split(frame.area());
+
+    let sequencer = app.sequencer.lock().unwrap();
+    let mut menus = vec![
+        ListItem::new(format!("Arpeggiator: {}", sequencer.arpeggiator.mode)),
+        ListItem::new(format!(
+            "Arpeggiator rate: {}",
+            sequencer.arpeggiator.rate_name()
+        )),
+        ListItem::new(match sequencer.transpose.enabled {
+            true => format!(
+                "Keyboard transpose: ON ({:+} degrees)",
+                sequencer.transpose.degrees
+            ),
+            false => "Keyboard transpose: OFF".to_string(),
+        }),
+        ListItem::new(format!("Transpose root key: {}", sequencer.transpose.root)),
+        ListItem::new(format!("Output: {}", sequencer.output.mode)),
+        ListItem::new(format!("Velocity scale: {}%", sequencer.output.velocity_scale)),
+        ListItem::new(format!("Velocity curve: {}", sequencer.output.velocity_curve)),
+        ListItem::new(format!("Pitch bend update rate: {} Hz", sequencer.pitch_update_rate)),
+        ListItem::new(format!("Clock: {} (applies on start)", sequencer.tempo.sync)),
+        ListItem::new(format!("Tempo: {:.0} BPM (internal clock)", sequencer.tempo.bpm)),
+    ];
+    drop(sequencer);
+
+    menus.push(ListItem::new("Exit"));
+
+    let list = List::new(menus)
+        .block(
+            Block::bordered()
+                .title(" Settings ".to_span().into_centered_line())
+                .style(Style::default().fg(Color::White)),
+        )
+        .style(Style::default().fg(Color::LightBlue))
+        .highlight_style(Style::default().fg(Color::Black).bg(Color::LightBlue))
+        .highlight_symbol("➤ ");
+
+    let menu_index = match &app.current_menu {
+        Menu::Settings { selected_menu } => Some(selected_menu.as_index()),
+        _ => None,
+    };
+
+    let mut state = ListState::default();
+    state.select(menu_index);
+
+    frame.render_stateful_widget(list, outer_layout[0], &mut state);
+}
+
+pub fn handle_key(app: &mut App, key_event: KeyEvent) -> Result<(), io::Error> {
+    if key_event.kind == KeyEventKind::Press {
+        return match key_event.code {
+            KeyCode::Enter => on_enter(app),
+            KeyCode::Esc => on_exit(app),
+            KeyCode::Up => on_up(app),
+            KeyCode::Down => on_down(app),
+            KeyCode::Left => on_change(app, false),
+            KeyCode::Right => on_change(app, true),
+            _ => Ok(()),
+        };
+    }
+    Ok(())
+}
+
+fn on_enter(app: &mut App) -> Result<(), io::Error> {
+    match &app.current_menu {
+        Menu::Settings {
+            selected_menu: SettingsMenuItem::Exit,
+        } => on_exit(app),
+        Menu::Settings { .. } => on_change(app, true),
+        _ => Ok(()),
+    }
+}
+
+fn on_exit(app: &mut App) -> Result<(), io::Error> {
+    app.current_menu = Menu::Main {
+        selected_menu: MainMenuItem::Settings,
+    };
+    Ok(())
+}
+
+fn on_up(app: &mut App) -> Result<(), io::Error> {
+    if let Menu::Settings { selected_menu } = &app.current_menu {
+        app.current_menu = Menu::Settings {
+            selected_menu: SettingsMenuItem::from_index(
+                (selected_menu.as_index() + SettingsMenuItem::length() - 1)
+                    % SettingsMenuItem::length(),
+            ),
+        };
+    }
+    Ok(())
+}
+
+fn on_down(app: &mut App) -> Result<(), io::Error> {
+    if let Menu::Settings { selected_menu } = &app.current_menu {
+        app.current_menu = Menu::Settings {
+            selected_menu: SettingsMenuItem::from_index(
+                (selected_menu.as_index() + 1) % SettingsMenuItem::length(),
+            ),
+        };
+    }
+    Ok(())
+}
+
+/// Changes the value of the selected setting, to the next one if `forward` is set.
+fn on_change(app: &mut App, forward: bool) -> Result<(), io::Error> {
+    let Menu::Settings { selected_menu } = &app.current_menu else {
+        return Err(io::Error::other("Out of bounds"));
+    };
+    let mut sequencer = app.sequencer.lock().unwrap();
+    let sequencer = &mut *sequencer;
+    let arpeggiator = &mut sequencer.arpeggiator;
+    let transpose = &mut sequencer.transpose;
+    match selected_menu {
+        SettingsMenuItem::ArpMode => {
+            arpeggiator.mode = if forward {
+                arpeggiator.mode.next()
+            } else {
+                arpeggiator.mode.previous()
+            };
+        }
+        SettingsMenuItem::ArpRate => {
+            arpeggiator.rate_index = if forward {
+                (arpeggiator.rate_index + 1) % ARP_RATES.len()
+            } else {
+                (arpeggiator.rate_index + ARP_RATES.len() - 1) % ARP_RATES.len()
+            };
+        }
+        SettingsMenuItem::Transpose => {
+            transpose.enabled = !transpose.enabled;
+            transpose.degrees = 0;
+        }
+        SettingsMenuItem::TransposeRoot => {
+            transpose.root = if forward {
+                transpose.root.saturating_add(1).min(127)
+            } else {
+                transpose.root.saturating_sub(1)
+            };
+        }
+        SettingsMenuItem::OutputMode => {
+            sequencer.output.mode = if forward {
+                sequencer.output.mode.next()
+            } else {
+                sequencer.output.mode.previous()
+            };
+        }
+        SettingsMenuItem::VelocityScale => {
+            sequencer.output.velocity_scale = if forward {
+                sequencer.output.velocity_scale.saturating_add(5).min(200)
+            } else {
+                sequencer.output.velocity_scale.saturating_sub(5)
+            };
+        }
+        SettingsMenuItem::VelocityCurve => {
+            sequencer.output.velocity_curve = if forward {
+                sequencer.output.velocity_curve.next()
+            } else {
+                sequencer.output.velocity_curve.previous()
+            };
+        }
+        SettingsMenuItem::PitchUpdateRate => {
+            let i = PITCH_UPDATE_RATES
+                .iter()
+                .position(|rate| *rate == sequencer.pitch_update_rate)
+                .unwrap_or(0);
+            sequencer.pitch_update_rate = if forward {
+                PITCH_UPDATE_RATES[(i + 1).min(PITCH_UPDATE_RATES.len() - 1)]
+            } else {
+                PITCH_UPDATE_RATES[i.saturating_sub(1)]
+            };
+        }
+        SettingsMenuItem::Sync => sequencer.tempo.sync = sequencer.tempo.sync.next(),
+        SettingsMenuItem::Tempo => {
+            let bpm = sequencer.tempo.bpm.round() + if forward { 1f64 } else { -1f64 };
+            sequencer.tempo.bpm = bpm.clamp(Tempo::MIN_BPM, Tempo::MAX_BPM);
+        }
+        SettingsMenuItem::Exit => {}
+    }
     Ok(())
 }
 
 pub fn move_to(app: &mut App) -> Result<(), io::Error> {
+    app.current_menu = Menu::Settings {
+        selected_menu: SettingsMenuItem::from_index(0),
+    };
     Ok(())
 }
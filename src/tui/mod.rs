@@ -1,9 +1,10 @@
+use crate::paths::DataPaths;
 use crate::sequencer::Sequencer;
 use crate::tui::entities::{App, Menu};
 use crate::tui::menus::sequencer_menu;
 use crate::tui::menus::{error_screen, link_controller_menu};
 use crate::tui::menus::{main_menu, settings_menu};
-use crossterm::event::{poll, read, Event, KeyCode, KeyEvent, KeyEventKind};
+use crossterm::event::{poll, read, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
 use crossterm::terminal::{
     disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen,
 };
@@ -15,7 +16,9 @@ use std::sync::atomic::Ordering;
 use std::sync::{Arc, Mutex};
 use crate::tui::error_handling::MidiSequencerTUIResult;
 
+mod clipboard;
 mod entities;
+mod file_watcher;
 mod menus;
 mod error_handling;
 
@@ -25,14 +28,20 @@ const ALLOWED_KEYS: &[KeyCode] = &[
     KeyCode::Char('n'),
 ];
 
-pub fn run_tui(sequencer: Arc<Mutex<Sequencer>>) -> Result<(), io::Error> {
+/// Runs the TUI until it is exited. `error` is shown first, for errors met before it started.
+pub fn run_tui(
+    sequencer: Arc<Mutex<Sequencer>>,
+    paths: DataPaths,
+    error: Option<io::Error>,
+) -> Result<(), io::Error> {
     enable_raw_mode()?;
     let mut stdout = io::stdout();
     execute!(stdout, EnterAlternateScreen)?;
     let backend = CrosstermBackend::new(stdout);
     let mut terminal = Terminal::new(backend)?;
 
-    let mut app = App::new(sequencer.clone());
+    let mut app = App::new(sequencer.clone(), paths);
+    app.error = error;
 
     purge_events()?;
 
@@ -46,6 +55,7 @@ pub fn run_tui(sequencer: Arc<Mutex<Sequencer>>) -> Result<(), io::Error> {
                 handle_key(&mut app, key_event).unwrap_or_display_err(&mut app);
             }
         }
+        file_watcher::check_files(&mut app).unwrap_or_display_err(&mut app);
     }
 
     disable_raw_mode()?;
@@ -69,21 +79,39 @@ fn handle_key(app: &mut App, key_event: KeyEvent) -> Result<(), io::Error> {
     match app.error {
         None => match &app.current_menu {
             Menu::Main { .. } => main_menu::handle_key(app, key_event),
+            Menu::Sequencer { .. } if is_history_key(&key_event) => {
+                let redo = key_event.code != KeyCode::Char('z')
+                    || key_event.modifiers.contains(KeyModifiers::SHIFT);
+                sequencer_menu::undo(app, redo)
+            }
             Menu::Sequencer { .. } => sequencer_menu::handle_key(app, key_event),
             Menu::LinkController => link_controller_menu::handle_key(app, key_event),
-            Menu::Settings => settings_menu::handle_key(app, key_event),
+            Menu::Settings { .. } => settings_menu::handle_key(app, key_event),
         },
-        Some(_) => Ok(error_screen::handle_key(app, key_event)),
+        Some(_) => {
+            error_screen::handle_key(app, key_event);
+            Ok(())
+        }
     }
 }
 
+/// Ctrl+Z undoes the last edit, Ctrl+Y or Ctrl+Shift+Z redoes it.
+fn is_history_key(key_event: &KeyEvent) -> bool {
+    key_event.kind == KeyEventKind::Press
+        && key_event.modifiers.contains(KeyModifiers::CONTROL)
+        && matches!(
+            key_event.code,
+            KeyCode::Char('z') | KeyCode::Char('Z') | KeyCode::Char('y')
+        )
+}
+
 fn handle_held_keys(app: &mut App, key_event: &KeyEvent) {
     if !ALLOWED_KEYS.contains(&key_event.code) {
         return;
     }
     match key_event.kind {
         KeyEventKind::Press => {
-            app.held_keys.insert(key_event.code.clone());
+            app.held_keys.insert(key_event.code);
         }
         KeyEventKind::Release => {
             app.held_keys.remove(&key_event.code);
@@ -98,7 +126,7 @@ fn draw_ui(frame: &mut Frame, app: &mut App) {
             Menu::Main { .. } => main_menu::draw(frame, app),
             Menu::Sequencer { .. } => sequencer_menu::draw(frame, app),
             Menu::LinkController => link_controller_menu::draw(frame),
-            Menu::Settings => settings_menu::draw(frame),
+            Menu::Settings { .. } => settings_menu::draw(frame, app),
         },
         Some(err) => {
             error_screen::draw(frame, err);
@@ -0,0 +1,246 @@
+use crate::json::LoadError;
+use crate::note::Scale;
+use crate::sequencer::Sequence;
+use std::fmt;
+use std::sync::Arc;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Severity {
+    /// The data plays, but probably not as intended.
+    Warning,
+    /// The data can't be played: loading it is refused.
+    Error,
+}
+
+#[derive(Debug)]
+pub struct Issue {
+    pub severity: Severity,
+    pub message: String,
+}
+
+impl fmt::Display for Issue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.severity {
+            Severity::Warning => write!(f, "warning: {}", self.message),
+            Severity::Error => write!(f, "error: {}", self.message),
+        }
+    }
+}
+
+/// The problems found in loaded scales and sequences.
+#[derive(Debug, Default)]
+pub struct Validation {
+    pub issues: Vec<Issue>,
+}
+
+impl Validation {
+    pub(crate) fn has_errors(&self) -> bool {
+        self.issues.iter().any(|issue| issue.severity == Severity::Error)
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.issues.is_empty()
+    }
+
+    fn warn(&mut self, message: String) {
+        self.issues.push(Issue {
+            severity: Severity::Warning,
+            message,
+        });
+    }
+
+    fn error(&mut self, message: String) {
+        self.issues.push(Issue {
+            severity: Severity::Error,
+            message,
+        });
+    }
+}
+
+impl fmt::Display for Validation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let issues: Vec<String> = self.issues.iter().map(|issue| issue.to_string()).collect();
+        write!(f, "{}", issues.join("\n"))
+    }
+}
+
+/// Checks the scales of a scale file.
+pub(crate) fn validate_scales(scales: &[Arc<Scale>]) -> Validation {
+    let mut validation = Validation::default();
+    if scales.is_empty() {
+        validation.error("no scales".to_string());
+    }
+    for (i, scale) in scales.iter().enumerate() {
+        validate_scale(scale, &mut validation);
+        if scales[..i].iter().any(|other| other.name == scale.name) {
+            validation.warn(format!(
+                "scale {}: another scale has the same name, sequences will use the first one",
+                scale.name
+            ));
+        }
+    }
+    validation
+}
+
+/// Checks the sequences of a sequence file. Their scales are checked with the scale file.
+pub(crate) fn validate_sequences(sequences: &[Sequence]) -> Validation {
+    let mut validation = Validation::default();
+    if sequences.is_empty() {
+        validation.error("no sequences".to_string());
+    }
+    for sequence in sequences {
+        validate_sequence(sequence, &mut validation);
+    }
+    validation
+}
+
+/// Refuses the data read from `path` if the validation found errors. Otherwise returns the
+/// warnings, to show them once the data is loaded.
+pub(crate) fn check(path: &str, validation: Validation) -> Result<Validation, LoadError> {
+    match validation.has_errors() {
+        true => Err(LoadError::Invalid {
+            path: path.to_string(),
+            validation,
+        }),
+        false => Ok(validation),
+    }
+}
+
+fn validate_scale(scale: &Scale, validation: &mut Validation) {
+    if scale.steps.is_empty() {
+        validation.error(format!("scale {}: no steps", scale.name));
+    }
+    if scale.note_names.len() != scale.steps.len() {
+        validation.error(format!(
+            "scale {}: {} note names for {} steps",
+            scale.name,
+            scale.note_names.len(),
+            scale.steps.len()
+        ));
+    }
+    if let Some(i) = (1..scale.steps.len()).find(|i| scale.steps[*i] <= scale.steps[i - 1]) {
+        validation.warn(format!(
+            "scale {}: step {} ({}) isn't above the step before it ({})",
+            scale.name,
+            i,
+            scale.steps[i],
+            scale.steps[i - 1]
+        ));
+    }
+}
+
+fn validate_sequence(sequence: &Sequence, validation: &mut Validation) {
+    if sequence.notes.is_empty() {
+        validation.error(format!("sequence {}: no notes", sequence.name));
+    }
+    for (i, note) in sequence.notes.iter().enumerate() {
+        let Some(step) = note.scale.steps.get(note.note_index) else {
+            validation.error(format!(
+                "sequence {}, note {}: note_index {} is past the {} degrees of scale {}",
+                sequence.name,
+                i,
+                note.note_index,
+                note.scale.steps.len(),
+                note.scale.name
+            ));
+            continue;
+        };
+        // Computed here rather than with `get_midi_number`, which high octaves overflow
+        let midi_number = step + note.octave as f64 * 12f64;
+        if !(0f64..128f64).contains(&midi_number) {
+            validation.error(format!(
+                "sequence {}, note {}: MIDI note {:.2} is outside 0-127",
+                sequence.name, i, midi_number
+            ));
+        }
+        let duration = note.duration.duration;
+        if !(duration > 0f64 && duration.is_finite()) {
+            validation.error(format!(
+                "sequence {}, note {}: duration {} isn't above 0",
+                sequence.name, i, duration
+            ));
+        }
+        if note.velocity > 127 {
+            validation.error(format!(
+                "sequence {}, note {}: velocity {} is above 127",
+                sequence.name, i, note.velocity
+            ));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::note::{Note, NoteDuration};
+
+    fn scale(steps: Vec<f64>) -> Arc<Scale> {
+        Arc::new(Scale {
+            name: "Scale".to_string(),
+            note_names: steps.iter().map(|step| step.to_string()).collect(),
+            steps,
+        })
+    }
+
+    fn sequence(notes: Vec<Note>) -> Sequence {
+        let scale = scale((0..12).map(|step| step as f64).collect());
+        Sequence::new("Sequence".to_string(), scale, notes)
+    }
+
+    fn note(note_index: usize, octave: u8, duration: f64) -> Note {
+        let scale = scale((0..12).map(|step| step as f64).collect());
+        Note::new(scale, note_index, octave, NoteDuration { duration }, 100)
+    }
+
+    fn messages(validation: &Validation) -> Vec<String> {
+        validation.issues.iter().map(|issue| issue.to_string()).collect()
+    }
+
+    #[test]
+    fn valid_sequences_have_no_issues() {
+        let validation = validate_sequences(&[sequence(vec![note(0, 5, 1f64)])]);
+        assert!(validation.is_empty());
+    }
+
+    #[test]
+    fn durations_must_be_above_zero() {
+        let notes = vec![note(0, 5, 1f64), note(0, 5, 0f64), note(0, 5, -0.5)];
+        let validation = validate_sequences(&[sequence(notes)]);
+        assert!(validation.has_errors());
+        assert_eq!(
+            messages(&validation),
+            [
+                "error: sequence Sequence, note 1: duration 0 isn't above 0",
+                "error: sequence Sequence, note 2: duration -0.5 isn't above 0",
+            ]
+        );
+    }
+
+    #[test]
+    fn notes_must_be_in_the_scale_and_the_midi_range() {
+        let notes = vec![note(12, 5, 1f64), note(8, 10, 1f64)];
+        let validation = validate_sequences(&[sequence(notes)]);
+        assert_eq!(
+            messages(&validation),
+            [
+                "error: sequence Sequence, note 0: note_index 12 is past the 12 degrees of scale \
+                 Scale",
+                "error: sequence Sequence, note 1: MIDI note 128.00 is outside 0-127",
+            ]
+        );
+    }
+
+    #[test]
+    fn scales_out_of_order_are_only_warned_about() {
+        let validation = validate_scales(&[scale(vec![0f64, 2f64, 1f64])]);
+        assert!(!validation.has_errors());
+        assert_eq!(validation.issues.len(), 1);
+        assert!(check("scales.json", validation).is_ok());
+    }
+
+    #[test]
+    fn empty_files_are_refused() {
+        assert!(check("scales.json", validate_scales(&[])).is_err());
+        assert!(check("sequences.json", validate_sequences(&[])).is_err());
+    }
+}